@@ -0,0 +1,594 @@
+//! Capture file reader - classic libpcap and pcapng containers
+
+use anyhow::{Context, Result};
+use std::fs::File;
+use std::io::{BufReader, ErrorKind, Read};
+
+/// Classic libpcap magic (microsecond timestamps)
+const PCAP_MAGIC: u32 = 0xa1b2c3d4;
+/// Classic libpcap magic (nanosecond timestamps)
+const PCAP_MAGIC_NANOS: u32 = 0xa1b23c4d;
+
+/// pcapng block types
+const BLOCK_SECTION_HEADER: u32 = 0x0A0D0D0A;
+const BLOCK_INTERFACE_DESCRIPTION: u32 = 0x00000001;
+const BLOCK_PACKET_OBSOLETE: u32 = 0x00000002;
+const BLOCK_SIMPLE_PACKET: u32 = 0x00000003;
+const BLOCK_ENHANCED_PACKET: u32 = 0x00000006;
+
+/// pcapng byte-order magic inside the Section Header Block
+const BYTE_ORDER_MAGIC: u32 = 0x1A2B3C4D;
+
+/// pcapng interface option codes
+const OPT_END_OF_OPT: u16 = 0;
+const OPT_IF_TSRESOL: u16 = 9;
+const OPT_IF_TSOFFSET: u16 = 14;
+
+/// Upper bound for a single block, protects against corrupt length fields
+const MAX_BLOCK_SIZE: usize = 64 * 1024 * 1024;
+
+/// One packet record read from a capture file
+#[derive(Debug, Clone)]
+pub struct CapturedPacket {
+    pub frame_number: u64, // 1-based record number in the file, as shown by Wireshark
+    pub timestamp_ns: u64, // capture time, nanoseconds since Unix epoch
+    pub link_type: u32,    // LINKTYPE_* value of the capturing interface
+    pub data: Vec<u8>,     // captured bytes, starting at the link-layer header
+}
+
+/// pcapng Interface Description Block state
+#[derive(Debug, Clone)]
+struct Interface {
+    link_type: u32,
+    snap_len: u32,
+    ts_resolution: TsResolution,
+    ts_offset_secs: i64,
+}
+
+/// Timestamp units of a pcapng interface (if_tsresol)
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum TsResolution {
+    Decimal(u8), // units of 10^-n seconds
+    Binary(u8),  // units of 2^-n seconds
+}
+
+impl TsResolution {
+    fn from_option(value: u8) -> Self {
+        if value & 0x80 == 0 {
+            TsResolution::Decimal(value)
+        } else {
+            TsResolution::Binary(value & 0x7f)
+        }
+    }
+
+    /// Convert a raw timestamp in interface units to nanoseconds
+    fn to_nanos(self, ts: u64) -> u64 {
+        let (num, den): (u128, u128) = match self {
+            TsResolution::Decimal(n) if n <= 9 => (10u128.pow(9 - n as u32), 1),
+            TsResolution::Decimal(n) => (1, 10u128.pow(n.min(38) as u32 - 9)),
+            TsResolution::Binary(n) => (1_000_000_000, 1u128 << n.min(127)),
+        };
+        (ts as u128 * num / den) as u64
+    }
+}
+
+#[derive(Debug)]
+enum Format {
+    Pcap {
+        link_type: u32,
+        big_endian: bool,
+        nanos: bool, // record sub-second field is nanoseconds instead of microseconds
+    },
+    PcapNg {
+        big_endian: bool,
+        interfaces: Vec<Interface>,
+    },
+}
+
+/// Sequential packet reader for classic pcap and pcapng files
+pub struct CaptureReader<R: Read> {
+    reader: R,
+    format: Format,
+    frames_read: u64,
+}
+
+impl CaptureReader<BufReader<File>> {
+    /// Open a capture file and detect its container format
+    pub fn open(path: &str) -> Result<Self> {
+        let file = File::open(path)
+            .with_context(|| format!("Failed to open file: {}", path))?;
+        Self::new(BufReader::new(file))
+    }
+}
+
+impl<R: Read> CaptureReader<R> {
+    /// Detect the container format from the leading magic number
+    pub fn new(mut reader: R) -> Result<Self> {
+        let mut magic = [0u8; 4];
+        reader.read_exact(&mut magic)
+            .context("Failed to read PCAP header")?;
+
+        let le_magic = u32::from_le_bytes(magic);
+        let format = match le_magic {
+            PCAP_MAGIC | PCAP_MAGIC_NANOS => Self::read_pcap_header(&mut reader, false, le_magic)?,
+            m if m.swap_bytes() == PCAP_MAGIC || m.swap_bytes() == PCAP_MAGIC_NANOS => {
+                Self::read_pcap_header(&mut reader, true, m.swap_bytes())?
+            }
+            BLOCK_SECTION_HEADER => {
+                let big_endian = read_section_header(&mut reader)?;
+                Format::PcapNg {
+                    big_endian,
+                    interfaces: Vec::new(),
+                }
+            }
+            _ => anyhow::bail!("Invalid PCAP file: bad magic number"),
+        };
+
+        Ok(CaptureReader {
+            reader,
+            format,
+            frames_read: 0,
+        })
+    }
+
+    /// Read the rest of the 24-byte classic pcap global header
+    fn read_pcap_header(reader: &mut R, big_endian: bool, magic: u32) -> Result<Format> {
+        let mut header = [0u8; 20];
+        reader.read_exact(&mut header)
+            .context("Failed to read PCAP header")?;
+
+        let endian = Endian { big: big_endian };
+        Ok(Format::Pcap {
+            link_type: endian.u32(&header[16..20]),
+            big_endian,
+            nanos: magic == PCAP_MAGIC_NANOS,
+        })
+    }
+
+    /// Read the next packet, or None at end of file
+    pub fn next_packet(&mut self) -> Result<Option<CapturedPacket>> {
+        let mut packet = match self.format {
+            Format::Pcap { .. } => self.next_pcap_packet()?,
+            Format::PcapNg { .. } => self.next_pcapng_packet()?,
+        };
+
+        if let Some(packet) = &mut packet {
+            self.frames_read += 1;
+            packet.frame_number = self.frames_read;
+        }
+
+        Ok(packet)
+    }
+
+    fn next_pcap_packet(&mut self) -> Result<Option<CapturedPacket>> {
+        let Format::Pcap { link_type, big_endian, nanos } = self.format else {
+            unreachable!()
+        };
+        let endian = Endian { big: big_endian };
+
+        // Read packet header (16 bytes)
+        let mut pkt_header = [0u8; 16];
+        if !read_or_eof(&mut self.reader, &mut pkt_header)? {
+            return Ok(None);
+        }
+
+        let ts_sec = endian.u32(&pkt_header[0..4]);
+        let ts_frac = endian.u32(&pkt_header[4..8]);
+        let incl_len = endian.u32(&pkt_header[8..12]);
+
+        if incl_len as usize > MAX_BLOCK_SIZE {
+            anyhow::bail!("Invalid PCAP file: packet length {} too large", incl_len);
+        }
+
+        // Read packet data
+        let mut data = vec![0u8; incl_len as usize];
+        self.reader.read_exact(&mut data)
+            .context("Failed to read packet data")?;
+
+        let frac_ns = if nanos { ts_frac as u64 } else { ts_frac as u64 * 1_000 };
+
+        Ok(Some(CapturedPacket {
+            frame_number: 0,
+            timestamp_ns: ts_sec as u64 * 1_000_000_000 + frac_ns,
+            link_type,
+            data,
+        }))
+    }
+
+    fn next_pcapng_packet(&mut self) -> Result<Option<CapturedPacket>> {
+        loop {
+            let mut block_header = [0u8; 8];
+            if !read_or_eof(&mut self.reader, &mut block_header)? {
+                return Ok(None);
+            }
+
+            let raw_type = u32::from_le_bytes([block_header[0], block_header[1], block_header[2], block_header[3]]);
+
+            // A new section may switch byte order and resets the interface list
+            if raw_type == BLOCK_SECTION_HEADER {
+                let big_endian = read_section_header_after_type(&mut self.reader, [
+                    block_header[4], block_header[5], block_header[6], block_header[7],
+                ])?;
+                self.format = Format::PcapNg {
+                    big_endian,
+                    interfaces: Vec::new(),
+                };
+                continue;
+            }
+
+            let Format::PcapNg { big_endian, ref mut interfaces } = self.format else {
+                unreachable!()
+            };
+            let endian = Endian { big: big_endian };
+
+            let block_type = endian.u32(&block_header[0..4]);
+            let total_len = endian.u32(&block_header[4..8]) as usize;
+            if !(12..=MAX_BLOCK_SIZE).contains(&total_len) || !total_len.is_multiple_of(4) {
+                anyhow::bail!("Invalid pcapng file: bad block length {}", total_len);
+            }
+
+            // Block body plus the trailing copy of the total length
+            let mut body = vec![0u8; total_len - 8];
+            self.reader.read_exact(&mut body)
+                .context("Failed to read pcapng block")?;
+            body.truncate(total_len - 12);
+
+            match block_type {
+                BLOCK_INTERFACE_DESCRIPTION => {
+                    interfaces.push(parse_interface(&body, endian)?);
+                }
+                BLOCK_ENHANCED_PACKET => {
+                    if body.len() < 20 {
+                        anyhow::bail!("Invalid pcapng file: truncated Enhanced Packet Block");
+                    }
+                    let interface_id = endian.u32(&body[0..4]) as usize;
+                    let ts = (endian.u32(&body[4..8]) as u64) << 32 | endian.u32(&body[8..12]) as u64;
+                    let cap_len = endian.u32(&body[12..16]) as usize;
+                    let interface = interfaces.get(interface_id).with_context(|| {
+                        format!("Invalid pcapng file: unknown interface {}", interface_id)
+                    })?;
+                    let data = body.get(20..20 + cap_len)
+                        .context("Invalid pcapng file: packet data exceeds block")?;
+
+                    return Ok(Some(CapturedPacket {
+                        frame_number: 0,
+                        timestamp_ns: interface.timestamp_ns(ts),
+                        link_type: interface.link_type,
+                        data: data.to_vec(),
+                    }));
+                }
+                BLOCK_SIMPLE_PACKET => {
+                    if body.len() < 4 {
+                        anyhow::bail!("Invalid pcapng file: truncated Simple Packet Block");
+                    }
+                    // Simple packets always belong to the first interface and carry no timestamp
+                    let interface = interfaces.first()
+                        .context("Invalid pcapng file: Simple Packet Block without interface")?;
+                    let orig_len = endian.u32(&body[0..4]) as usize;
+                    let mut cap_len = orig_len.min(body.len() - 4);
+                    if interface.snap_len > 0 {
+                        cap_len = cap_len.min(interface.snap_len as usize);
+                    }
+
+                    return Ok(Some(CapturedPacket {
+                        frame_number: 0,
+                        timestamp_ns: 0,
+                        link_type: interface.link_type,
+                        data: body[4..4 + cap_len].to_vec(),
+                    }));
+                }
+                BLOCK_PACKET_OBSOLETE => {
+                    if body.len() < 20 {
+                        anyhow::bail!("Invalid pcapng file: truncated Packet Block");
+                    }
+                    let interface_id = endian.u16(&body[0..2]) as usize;
+                    let ts = (endian.u32(&body[4..8]) as u64) << 32 | endian.u32(&body[8..12]) as u64;
+                    let cap_len = endian.u32(&body[12..16]) as usize;
+                    let interface = interfaces.get(interface_id).with_context(|| {
+                        format!("Invalid pcapng file: unknown interface {}", interface_id)
+                    })?;
+                    let data = body.get(20..20 + cap_len)
+                        .context("Invalid pcapng file: packet data exceeds block")?;
+
+                    return Ok(Some(CapturedPacket {
+                        frame_number: 0,
+                        timestamp_ns: interface.timestamp_ns(ts),
+                        link_type: interface.link_type,
+                        data: data.to_vec(),
+                    }));
+                }
+                // Name resolution, statistics, custom blocks, ... are not needed
+                _ => {}
+            }
+        }
+    }
+}
+
+impl Interface {
+    fn timestamp_ns(&self, ts: u64) -> u64 {
+        let nanos = self.ts_resolution.to_nanos(ts) as i128;
+        (nanos + self.ts_offset_secs as i128 * 1_000_000_000).max(0) as u64
+    }
+}
+
+/// Byte order of the current pcapng section
+#[derive(Debug, Clone, Copy)]
+struct Endian {
+    big: bool,
+}
+
+impl Endian {
+    fn u16(self, b: &[u8]) -> u16 {
+        let b = [b[0], b[1]];
+        if self.big { u16::from_be_bytes(b) } else { u16::from_le_bytes(b) }
+    }
+
+    fn u32(self, b: &[u8]) -> u32 {
+        let b = [b[0], b[1], b[2], b[3]];
+        if self.big { u32::from_be_bytes(b) } else { u32::from_le_bytes(b) }
+    }
+
+    fn i64(self, b: &[u8]) -> i64 {
+        let b = [b[0], b[1], b[2], b[3], b[4], b[5], b[6], b[7]];
+        if self.big { i64::from_be_bytes(b) } else { i64::from_le_bytes(b) }
+    }
+}
+
+/// Fill `buf` completely; Ok(false) on a clean end of file before any byte
+fn read_or_eof<R: Read>(reader: &mut R, buf: &mut [u8]) -> Result<bool> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..]) {
+            Ok(0) if filled == 0 => return Ok(false),
+            Ok(0) => return Err(std::io::Error::from(ErrorKind::UnexpectedEof))
+                .context("Truncated capture file"),
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == ErrorKind::Interrupted => {}
+            Err(e) => return Err(e).context("Failed to read capture file"),
+        }
+    }
+    Ok(true)
+}
+
+/// Read a Section Header Block whose type field was already consumed
+fn read_section_header<R: Read>(reader: &mut R) -> Result<bool> {
+    let mut len = [0u8; 4];
+    reader.read_exact(&mut len)
+        .context("Failed to read pcapng section header")?;
+    read_section_header_after_type(reader, len)
+}
+
+/// Read the rest of a Section Header Block given its raw length field;
+/// returns true when the section is big-endian
+fn read_section_header_after_type<R: Read>(reader: &mut R, raw_len: [u8; 4]) -> Result<bool> {
+    let mut magic = [0u8; 4];
+    reader.read_exact(&mut magic)
+        .context("Failed to read pcapng section header")?;
+
+    let big_endian = match u32::from_le_bytes(magic) {
+        BYTE_ORDER_MAGIC => false,
+        m if m.swap_bytes() == BYTE_ORDER_MAGIC => true,
+        _ => anyhow::bail!("Invalid pcapng file: bad byte-order magic"),
+    };
+    let endian = Endian { big: big_endian };
+
+    let total_len = endian.u32(&raw_len) as usize;
+    if !(28..=MAX_BLOCK_SIZE).contains(&total_len) || !total_len.is_multiple_of(4) {
+        anyhow::bail!("Invalid pcapng file: bad section header length {}", total_len);
+    }
+
+    // Version, section length, options and trailing length are not needed
+    let mut rest = vec![0u8; total_len - 12];
+    reader.read_exact(&mut rest)
+        .context("Failed to read pcapng section header")?;
+
+    Ok(big_endian)
+}
+
+/// Parse an Interface Description Block body
+fn parse_interface(body: &[u8], endian: Endian) -> Result<Interface> {
+    if body.len() < 8 {
+        anyhow::bail!("Invalid pcapng file: truncated Interface Description Block");
+    }
+
+    let mut interface = Interface {
+        link_type: endian.u16(&body[0..2]) as u32,
+        snap_len: endian.u32(&body[4..8]),
+        ts_resolution: TsResolution::Decimal(6),
+        ts_offset_secs: 0,
+    };
+
+    // Options: code (2), length (2), value padded to 4 bytes
+    let mut offset = 8;
+    while offset + 4 <= body.len() {
+        let code = endian.u16(&body[offset..offset + 2]);
+        let len = endian.u16(&body[offset + 2..offset + 4]) as usize;
+        let value = &body[offset + 4..(offset + 4 + len).min(body.len())];
+
+        match code {
+            OPT_END_OF_OPT => break,
+            OPT_IF_TSRESOL if !value.is_empty() => {
+                interface.ts_resolution = TsResolution::from_option(value[0]);
+            }
+            OPT_IF_TSOFFSET if value.len() >= 8 => {
+                interface.ts_offset_secs = endian.i64(value);
+            }
+            _ => {}
+        }
+
+        offset += 4 + len.div_ceil(4) * 4;
+    }
+
+    Ok(interface)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    /// Build a pcapng block with the given byte order
+    fn block(big: bool, block_type: u32, body: &[u8]) -> Vec<u8> {
+        let u32b = |v: u32| if big { v.to_be_bytes() } else { v.to_le_bytes() };
+        let mut padded = body.to_vec();
+        padded.resize(body.len().div_ceil(4) * 4, 0);
+        let total = (padded.len() + 12) as u32;
+
+        let mut out = Vec::new();
+        out.extend_from_slice(&u32b(block_type));
+        out.extend_from_slice(&u32b(total));
+        out.extend_from_slice(&padded);
+        out.extend_from_slice(&u32b(total));
+        out
+    }
+
+    fn section_header(big: bool) -> Vec<u8> {
+        let mut body = Vec::new();
+        if big {
+            body.extend_from_slice(&BYTE_ORDER_MAGIC.to_be_bytes());
+            body.extend_from_slice(&1u16.to_be_bytes());
+            body.extend_from_slice(&0u16.to_be_bytes());
+        } else {
+            body.extend_from_slice(&BYTE_ORDER_MAGIC.to_le_bytes());
+            body.extend_from_slice(&1u16.to_le_bytes());
+            body.extend_from_slice(&0u16.to_le_bytes());
+        }
+        body.extend_from_slice(&(-1i64).to_le_bytes());
+        block(big, BLOCK_SECTION_HEADER, &body)
+    }
+
+    fn interface(big: bool, link_type: u16, tsresol: Option<u8>) -> Vec<u8> {
+        let u16b = |v: u16| if big { v.to_be_bytes() } else { v.to_le_bytes() };
+        let mut body = Vec::new();
+        body.extend_from_slice(&u16b(link_type));
+        body.extend_from_slice(&[0, 0]);
+        body.extend_from_slice(&if big { 65535u32.to_be_bytes() } else { 65535u32.to_le_bytes() });
+        if let Some(res) = tsresol {
+            body.extend_from_slice(&u16b(OPT_IF_TSRESOL));
+            body.extend_from_slice(&u16b(1));
+            body.extend_from_slice(&[res, 0, 0, 0]);
+            body.extend_from_slice(&u16b(OPT_END_OF_OPT));
+            body.extend_from_slice(&u16b(0));
+        }
+        block(big, BLOCK_INTERFACE_DESCRIPTION, &body)
+    }
+
+    fn enhanced_packet(big: bool, interface_id: u32, ts: u64, data: &[u8]) -> Vec<u8> {
+        let u32b = |v: u32| if big { v.to_be_bytes() } else { v.to_le_bytes() };
+        let mut body = Vec::new();
+        body.extend_from_slice(&u32b(interface_id));
+        body.extend_from_slice(&u32b((ts >> 32) as u32));
+        body.extend_from_slice(&u32b(ts as u32));
+        body.extend_from_slice(&u32b(data.len() as u32));
+        body.extend_from_slice(&u32b(data.len() as u32));
+        body.extend_from_slice(data);
+        block(big, BLOCK_ENHANCED_PACKET, &body)
+    }
+
+    fn read_all(bytes: Vec<u8>) -> Vec<CapturedPacket> {
+        let mut reader = CaptureReader::new(Cursor::new(bytes)).unwrap();
+        let mut packets = Vec::new();
+        while let Some(packet) = reader.next_packet().unwrap() {
+            packets.push(packet);
+        }
+        packets
+    }
+
+    #[test]
+    fn test_pcapng_multiple_interfaces() {
+        let mut file = section_header(false);
+        file.extend(interface(false, 1, None));     // Ethernet, microseconds
+        file.extend(interface(false, 101, Some(9))); // Raw IP, nanoseconds
+        file.extend(enhanced_packet(false, 0, 1_500_000, b"abc"));
+        file.extend(block(false, 0x00000005, &[0u8; 8])); // statistics block, skipped
+        file.extend(enhanced_packet(false, 1, 1_500_000, b"defgh"));
+
+        let packets = read_all(file);
+        assert_eq!(packets.len(), 2);
+        assert_eq!(packets[0].link_type, 1);
+        assert_eq!(packets[0].timestamp_ns, 1_500_000_000);
+        assert_eq!(packets[0].data, b"abc");
+        assert_eq!(packets[1].link_type, 101);
+        assert_eq!(packets[1].timestamp_ns, 1_500_000);
+        assert_eq!(packets[1].frame_number, 2);
+        assert_eq!(packets[1].data, b"defgh");
+    }
+
+    #[test]
+    fn test_pcapng_big_endian_and_simple_packet() {
+        let mut file = section_header(true);
+        file.extend(interface(true, 1, Some(0x80 | 10))); // 2^-10 seconds
+        file.extend(enhanced_packet(true, 0, 2048, b"xyz"));
+        let mut spb = Vec::new();
+        spb.extend_from_slice(&4u32.to_be_bytes());
+        spb.extend_from_slice(b"spb!");
+        file.extend(block(true, BLOCK_SIMPLE_PACKET, &spb));
+
+        let packets = read_all(file);
+        assert_eq!(packets.len(), 2);
+        assert_eq!(packets[0].timestamp_ns, 2_000_000_000);
+        assert_eq!(packets[0].data, b"xyz");
+        assert_eq!(packets[1].data, b"spb!");
+    }
+
+    #[test]
+    fn test_pcapng_new_section_resets_interfaces() {
+        let mut file = section_header(false);
+        file.extend(interface(false, 1, None));
+        file.extend(enhanced_packet(false, 0, 1, b"a"));
+        file.extend(section_header(true));
+        file.extend(interface(true, 228, None));
+        file.extend(enhanced_packet(true, 0, 1, b"b"));
+
+        let packets = read_all(file);
+        assert_eq!(packets[0].link_type, 1);
+        assert_eq!(packets[1].link_type, 228);
+        assert_eq!(packets[1].data, b"b");
+    }
+
+    /// Build a classic pcap file with the given magic and byte order
+    fn classic_pcap(big: bool, magic: u32, records: &[(u32, u32, &[u8])]) -> Vec<u8> {
+        let u32b = |v: u32| if big { v.to_be_bytes() } else { v.to_le_bytes() };
+        let u16b = |v: u16| if big { v.to_be_bytes() } else { v.to_le_bytes() };
+
+        let mut out = Vec::new();
+        out.extend_from_slice(&u32b(magic));
+        out.extend_from_slice(&u16b(2));
+        out.extend_from_slice(&u16b(4));
+        out.extend_from_slice(&[0u8; 8]);
+        out.extend_from_slice(&u32b(65535));
+        out.extend_from_slice(&u32b(113)); // LINKTYPE_LINUX_SLL
+        for &(sec, frac, data) in records {
+            out.extend_from_slice(&u32b(sec));
+            out.extend_from_slice(&u32b(frac));
+            out.extend_from_slice(&u32b(data.len() as u32));
+            out.extend_from_slice(&u32b(data.len() as u32));
+            out.extend_from_slice(data);
+        }
+        out
+    }
+
+    #[test]
+    fn test_pcap_byte_order_and_resolution() {
+        let records: &[(u32, u32, &[u8])] = &[(10, 250, b"first"), (11, 999, b"second!")];
+
+        for big in [false, true] {
+            let packets = read_all(classic_pcap(big, PCAP_MAGIC, records));
+            assert_eq!(packets.len(), 2);
+            assert_eq!(packets[0].link_type, 113);
+            assert_eq!(packets[0].timestamp_ns, 10_000_250_000);
+            assert_eq!(packets[1].data, b"second!");
+
+            let packets = read_all(classic_pcap(big, PCAP_MAGIC_NANOS, records));
+            assert_eq!(packets[0].timestamp_ns, 10_000_000_250);
+            assert_eq!(packets[1].timestamp_ns, 11_000_000_999);
+            assert_eq!(packets[1].data, b"second!");
+        }
+    }
+
+    #[test]
+    fn test_bad_magic() {
+        let result = CaptureReader::new(Cursor::new(vec![0u8; 24]));
+        assert!(result.is_err());
+    }
+}
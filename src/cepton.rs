@@ -1,4 +1,7 @@
-/// Cepton STDV packet and point data structures
+//! Cepton STDV packet and point data structures
+
+use crate::transform::CoordFrame;
+use std::net::SocketAddr;
 
 /// Parse mode for different point data formats
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -7,8 +10,36 @@ pub enum ParseMode {
     Debug,   // 17 bytes per point, 72 points per packet
 }
 
+impl ParseMode {
+    /// Bytes per point for this layout
+    pub fn point_size(self) -> usize {
+        match self {
+            ParseMode::Normal => 10,
+            ParseMode::Debug => 17,
+        }
+    }
+
+    /// Layout matching the STDV header's point_size field
+    pub fn from_point_size(point_size: u8) -> Option<Self> {
+        match point_size {
+            10 => Some(ParseMode::Normal),
+            17 => Some(ParseMode::Debug),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for ParseMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ParseMode::Normal => write!(f, "Normal ({} bytes/point)", self.point_size()),
+            ParseMode::Debug => write!(f, "Debug ({} bytes/point)", self.point_size()),
+        }
+    }
+}
+
 /// Represents a 3D point with XYZ coordinates in meters and additional metadata
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct Point {
     pub x: f64,           // meters
     pub y: f64,           // meters
@@ -20,6 +51,71 @@ pub struct Point {
     pub distance: Option<u32>,    // Debug: distance in mm or raw units
     pub intensity: Option<u16>,   // Debug: intensity value
     pub power_level: Option<u8>,  // Debug: power level
+
+    pub timestamp_us: u64,        // sensor time: header timestamp + point offset, microseconds since boot
+    pub capture_time_ns: u64,     // pcap capture time of the packet, nanoseconds since Unix epoch
+
+    // Trace-back context
+    pub channel: u8,              // laser ID
+    pub sequence_id: u32,         // STDV packet sequence ID
+    pub packet_index: u64,        // 1-based frame number of the packet in the pcap (Wireshark "No.")
+    pub point_index: u16,         // 0-based index of the point within its packet
+    pub frame: u64,               // scan frame, 0 unless frames are split
+
+    pub sensor_xyz: Option<[f64; 3]>, // sensor frame position once x, y, z are transformed
+}
+
+/// Euclidean distance of a position from its frame's origin, meters
+pub fn range([x, y, z]: [f64; 3]) -> f64 {
+    (x * x + y * y + z * z).sqrt()
+}
+
+/// Horizontal angle of a position in degrees: 0 straight ahead (+Y), positive towards +X
+pub fn azimuth([x, y, _]: [f64; 3]) -> f64 {
+    x.atan2(y).to_degrees()
+}
+
+/// Vertical angle of a position in degrees above the horizontal plane
+pub fn elevation([x, y, z]: [f64; 3]) -> f64 {
+    z.atan2(x.hypot(y)).to_degrees()
+}
+
+impl Point {
+    /// Position in `frame`, meters; the vehicle frame is x, y, z as exported
+    pub fn position(&self, frame: CoordFrame) -> [f64; 3] {
+        match (frame, self.sensor_xyz) {
+            (CoordFrame::Sensor, Some(sensor_xyz)) => sensor_xyz,
+            _ => [self.x, self.y, self.z],
+        }
+    }
+
+    /// Euclidean distance from the sensor, meters
+    pub fn range(&self) -> f64 {
+        range(self.position(CoordFrame::Sensor))
+    }
+
+    /// Horizontal angle in degrees, seen from the sensor: 0 straight ahead (+Y), positive towards +X
+    pub fn azimuth(&self) -> f64 {
+        azimuth(self.position(CoordFrame::Sensor))
+    }
+
+    /// Vertical angle in degrees above the sensor's horizontal plane
+    pub fn elevation(&self) -> f64 {
+        elevation(self.position(CoordFrame::Sensor))
+    }
+
+    /// Computed range minus the Debug mode distance field, meters, with `unit` meters per
+    /// distance count; None without debug data
+    pub fn range_error(&self, unit: f64) -> Option<f64> {
+        self.distance.map(|distance| self.range() - distance as f64 * unit)
+    }
+}
+
+/// Identifies one channel of one sensor: the sensor's UDP source endpoint plus laser ID
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ChannelKey {
+    pub sensor: SocketAddr, // sensor source address
+    pub channel: u8,        // laser ID
 }
 
 /// STDV packet header (24 bytes)
@@ -184,7 +280,9 @@ impl RawPoint {
 
     /// Convert raw point to meters with all metadata
     /// Cepton uses 0.5cm (0.005m) resolution for coordinates
-    pub fn to_meters(&self) -> Point {
+    /// `header` is the STDV header of the packet, `capture_time_ns` the pcap record time;
+    /// packet_index, point_index and frame are left for the caller to fill in
+    pub fn to_meters(&self, header: &StdvHeader, capture_time_ns: u64) -> Point {
         const SCALE: f64 = 0.005; // 0.5cm = 0.005m
 
         Point {
@@ -196,6 +294,14 @@ impl RawPoint {
             distance: self.distance,
             intensity: self.intensity,
             power_level: self.power_level,
+            timestamp_us: header.timestamp + self.timestamp as u64,
+            capture_time_ns,
+            channel: self.laser_id,
+            sequence_id: header.sequence_id,
+            packet_index: 0,
+            point_index: 0,
+            frame: 0,
+            sensor_xyz: None,
         }
     }
 
@@ -220,6 +326,14 @@ mod tests {
         assert_eq!(header.point_count, 144);
     }
 
+    #[test]
+    fn test_parse_mode_point_size() {
+        assert_eq!(ParseMode::from_point_size(10), Some(ParseMode::Normal));
+        assert_eq!(ParseMode::from_point_size(17), Some(ParseMode::Debug));
+        assert_eq!(ParseMode::from_point_size(12), None);
+        assert_eq!(ParseMode::Debug.point_size(), 17);
+    }
+
     #[test]
     fn test_raw_point_parse() {
         // Test data: x=2560 (12.8m), y=144 (0.72m), z=-17788 (-88.94m)
@@ -231,9 +345,33 @@ mod tests {
         assert_eq!(point.z, -17788);
         assert_eq!(point.laser_id, 5);
 
-        let meters = point.to_meters();
+        let mut header_data = vec![0u8; 24];
+        header_data[0..4].copy_from_slice(b"STDV");
+        header_data[8..16].copy_from_slice(&1_000_000u64.to_le_bytes());
+        header_data[20..24].copy_from_slice(&42u32.to_le_bytes());
+        let header = StdvHeader::parse(&header_data).unwrap();
+
+        let meters = point.to_meters(&header, 1_700_000_000_000_000_000);
         assert!((meters.x - 12.8).abs() < 0.01);
         assert!((meters.y - 0.72).abs() < 0.01);
+        assert_eq!(meters.timestamp_us, 1_000_002);
+        assert_eq!(meters.capture_time_ns, 1_700_000_000_000_000_000);
+        assert_eq!(meters.channel, 5);
+        assert_eq!(meters.sequence_id, 42);
+    }
+
+    #[test]
+    fn test_point_polar() {
+        let point = Point { x: 1.0, y: 1.0, z: 2.0_f64.sqrt(), ..Default::default() };
+        assert!((point.range() - 2.0).abs() < 1e-12);
+        assert!((point.azimuth() - 45.0).abs() < 1e-12);
+        assert!((point.elevation() - 45.0).abs() < 1e-12);
+        assert!((Point { x: -1.0, y: -1.0, ..Default::default() }.azimuth() + 135.0).abs() < 1e-12);
+
+        assert_eq!(point.range_error(0.001), None);
+        let debug = Point { y: 2.5, distance: Some(2_480), ..Default::default() };
+        assert!((debug.range_error(0.001).unwrap() - 0.02).abs() < 1e-9);
+        assert!((debug.range_error(0.01).unwrap() + 22.3).abs() < 1e-9);
     }
 
     #[test]
@@ -247,7 +385,8 @@ mod tests {
         assert_eq!(point.y, 17863);
         assert_eq!(point.z, -17079);
 
-        let meters = point.to_meters();
+        let header = StdvHeader::parse(b"STDV\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0").unwrap();
+        let meters = point.to_meters(&header, 0);
         assert!((meters.x - 33.545).abs() < 0.01);
         assert!((meters.y - 89.315).abs() < 0.01);
         assert!((meters.z - (-85.395)).abs() < 0.01);
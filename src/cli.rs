@@ -0,0 +1,911 @@
+//! Command-line argument parsing for non-interactive runs
+
+use crate::cepton::ParseMode;
+use crate::columns::ColumnOptions;
+use crate::error::ConvertError;
+use crate::excel_exporter::SplitMode;
+use crate::export::{Layout, OutputFormat};
+use crate::flags::{self, FlagMask};
+use crate::frames::FrameMode;
+use crate::pcd_exporter::PcdEncoding;
+use crate::ply_exporter::PlyEncoding;
+use crate::pcap_reader::PacketFilter;
+use crate::region::{Region, RegionFilter};
+use crate::time_window::{self, TimeBase, TimeBound, TimeWindow};
+use anyhow::Context;
+use crate::transform::{CoordFrame, SensorId, SensorTransforms, Transform};
+use std::net::{IpAddr, SocketAddr};
+use std::ops::RangeInclusive;
+use std::path::Path;
+
+pub const USAGE: &str = "\
+Usage: pcap_xyz_extractor [INPUT] [OPTIONS]
+
+Run without arguments to start the interactive wizard.
+
+Arguments:
+  INPUT                     PCAP file to convert
+
+Options:
+  -m, --mode <MODE>         Point format: auto | normal | debug [default: auto]
+                            auto reads the layout from each packet header;
+                            normal/debug override the header
+  -c, --channels <SPEC>     Channels to extract, e.g. 0,5,10-20 or all [default: all]
+  -o, --output <PATH>       Output file [default: <INPUT>_xyz.<FORMAT>]
+  -f, --format <FORMAT>     Output format: xlsx | csv | tsv | pcd | ply | las | parquet | npy | npz
+                            [default: from the output extension, else xlsx]
+      --layout <LAYOUT>     Files of the non-Excel formats: per-channel | merged [default: per-channel]
+                            merged writes one file with a channel column
+      --pcd-encoding <ENC>  PCD data encoding: ascii | binary | binary_compressed [default: binary]
+                            binary_compressed keeps each file's points in memory until the end
+                            (25 bytes per point, 37 with --polar-columns; at most 171M / 116M points)
+      --ply-encoding <ENC>  PLY data encoding: ascii | binary [default: binary]
+  -y, --yes                 Overwrite the output file if it already exists
+      --src-ip <IP>         Only use packets sent from this sensor address
+      --src-port <PORT>     Only use packets sent from this UDP port
+      --dst-port <PORT>     Only use packets sent to this UDP port
+      --start <TIME>        Skip packets before TIME (see --time-base)
+      --end <TIME>          Skip packets after TIME (see --time-base)
+      --time-base <BASE>    Clock of --start/--end: offset | sensor | capture [default: offset]
+                            offset: seconds after the first packet, e.g. 12.5
+                            sensor: seconds of STDV header time, e.g. 3605.2
+                            capture: [YYYY-MM-DD ]HH:MM:SS[.fff][Z|+HH:MM], UTC unless
+                            an offset is given, or Unix seconds
+      --roi-box <X0,Y0,Z0,X1,Y1,Z1>
+                            Only keep points inside this box (meters)
+      --roi-cylinder <R0,R1[,Z0,Z1]>
+                            Only keep points whose horizontal distance is R0 to R1 m
+                            (and whose height is Z0 to Z1 m)
+      --roi-range <R0,R1>   Only keep points R0 to R1 m from the origin
+      --roi-sector <A0,A1[,E0,E1]>
+                            Only keep points at azimuth A0 to A1 degrees (0 = +Y, positive
+                            towards +X; A0 > A1 wraps through 180) and elevation E0 to E1
+                            The --roi-* options can be repeated; a point must lie in all regions
+      --roi-frame <FRAME>   Frame every --roi-* region is tested in: vehicle | sensor
+                            vehicle: X/Y/Z as exported, after --transform [default]
+                            sensor: the sensor's own frame, as the polar columns
+      --transform <[SENSOR=]X,Y,Z,ROLL,PITCH,YAW>
+                            Move the points of SENSOR (IP or IP:port; all other sensors if
+                            omitted) into the vehicle frame: mounting position in meters,
+                            roll about +Y, pitch about +X, yaw about +Z in degrees.
+                            16 values instead are a row-major 4x4 rigid matrix. Can be repeated
+      --transform-file <PATH>
+                            Read --transform values from a file, one per line, # comments;
+                            --transform values override the file's
+      --per-sensor-files    Write one workbook per sensor instead of one sheet set per sensor
+      --time-columns        Add sensor time (us) and capture time (Unix s) columns
+      --trace-columns       Add channel, sequence ID, packet number and point index columns
+      --flag-columns        Add one 0/1 column per point flag bit (saturated, blooming, ...)
+      --drop-flags <LIST>   Drop points with any of these flags, e.g. no_return,saturated
+                            Flags: saturated, blooming, frame_parity, frame_boundary,
+                            second_return, no_return, noise, blocked
+      --polar-columns       Add range (m), azimuth and elevation (deg) columns; the PCD, PLY,
+                            LAS, Parquet and NumPy formats get them as extra fields.
+                            Excel and CSV also get range - Debug distance for Debug points
+      --debug-distance-unit <UNIT>
+                            Unit of the Debug distance field: mm | cm | m | <meters per count>
+                            [default: mm]
+      --split <MODE>        Where channels over Excel's row limit continue: sheets | workbooks
+                            sheets adds Channel_N_part2, ... sheets [default];
+                            workbooks writes <OUTPUT>_part2.xlsx, ...
+      --frames <MODE>       Split each sensor's stream into scan frames: wrap | <MS>
+                            wrap starts a frame when the scan pattern jumps back;
+                            <MS> starts one every MS milliseconds of sensor time
+      --frame-range <N-M>   Only export frames N to M, e.g. 10-20 or 7 (needs --frames)
+      --frame-files         Write each frame to its own <OUTPUT>_Frame_N files (needs --frames)
+  -h, --help                Print this help
+
+Exit codes:
+  0  success
+  1  other error
+  2  invalid arguments
+  3  no STDV packets found
+  4  no channels matched the selection
+  5  I/O failure";
+
+/// Options for a non-interactive run
+#[derive(Debug, Clone, PartialEq)]
+pub struct CliOptions {
+    pub input: String,
+    pub mode: Option<ParseMode>, // None: detect from each packet header
+    pub channels: String, // raw channel spec, resolved after the scan
+    pub output: Option<String>,
+    pub overwrite: bool,
+    pub filter: PacketFilter,
+    pub region: RegionFilter,
+    pub transforms: SensorTransforms,
+    pub transform_files: Vec<String>, // read at the start of the run; `transforms` override them
+    pub drop_flags: FlagMask,
+    pub per_sensor_files: bool,
+    pub columns: ColumnOptions,
+    pub split: SplitMode,
+    pub format: Option<OutputFormat>, // None: from the output extension
+    pub layout: Layout,
+    pub pcd_encoding: PcdEncoding,
+    pub ply_encoding: PlyEncoding,
+    pub frames: Option<FrameMode>, // None: no frame segmentation
+    pub frame_range: Option<RangeInclusive<u64>>,
+    pub frame_files: bool,
+}
+
+impl CliOptions {
+    /// Output format: --format, else the output file extension, else xlsx
+    pub fn output_format(&self) -> OutputFormat {
+        self.format
+            .or_else(|| self.output.as_deref().and_then(OutputFormat::from_path))
+            .unwrap_or_default()
+    }
+}
+
+/// Result of parsing the command line
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    Run(Box<CliOptions>),
+    Help,
+}
+
+/// Parse command-line arguments (without the program name)
+pub fn parse_args(args: &[String]) -> Result<Command, ConvertError> {
+    let mut input: Option<String> = None;
+    let mut mode = None;
+    let mut channels = String::from("all");
+    let mut output = None;
+    let mut overwrite = false;
+    let mut filter = PacketFilter::default();
+    let mut start = None;
+    let mut end = None;
+    let mut time_base = TimeBase::default();
+    let mut region = RegionFilter::default();
+    let mut transforms = SensorTransforms::default();
+    let mut transform_files = Vec::new();
+    let mut drop_flags = FlagMask::default();
+    let mut per_sensor_files = false;
+    let mut columns = ColumnOptions::default();
+    let mut split = SplitMode::default();
+    let mut format = None;
+    let mut layout = Layout::default();
+    let mut pcd_encoding = PcdEncoding::default();
+    let mut ply_encoding = PlyEncoding::default();
+    let mut frames = None;
+    let mut frame_range = None;
+    let mut frame_files = false;
+
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        // Accept both "--flag value" and "--flag=value"
+        let (flag, inline_value) = match arg.split_once('=') {
+            Some((f, v)) if f.starts_with("--") => (f, Some(v.to_string())),
+            _ => (arg.as_str(), None),
+        };
+
+        let mut value = |name: &str| -> Result<String, ConvertError> {
+            match inline_value.clone() {
+                Some(v) => Ok(v),
+                None => iter
+                    .next()
+                    .cloned()
+                    .ok_or_else(|| ConvertError::Usage(format!("Missing value for {}", name))),
+            }
+        };
+
+        match flag {
+            "-h" | "--help" => return Ok(Command::Help),
+            "-m" | "--mode" => mode = parse_mode(&value(flag)?)?,
+            "-c" | "--channels" => channels = value(flag)?,
+            "-o" | "--output" => output = Some(value(flag)?),
+            "-y" | "--yes" => overwrite = true,
+            "--src-ip" => filter.src_ip = Some(parse_value::<IpAddr>(flag, &value(flag)?)?),
+            "--src-port" => filter.src_port = Some(parse_value::<u16>(flag, &value(flag)?)?),
+            "--dst-port" => filter.dst_port = Some(parse_value::<u16>(flag, &value(flag)?)?),
+            "--start" => start = Some(value(flag)?),
+            "--end" => end = Some(value(flag)?),
+            "--time-base" => time_base = parse_time_base(&value(flag)?)?,
+            "--roi-box" | "--roi-cylinder" | "--roi-range" | "--roi-sector" => {
+                region.regions.push(parse_region(flag, &value(flag)?)?);
+            }
+            "--roi-frame" => region.frame = parse_roi_frame(&value(flag)?)?,
+            "--transform" => {
+                let (sensor, transform) = parse_transform(&value(flag)?)?;
+                transforms.insert(sensor, transform);
+            }
+            "--transform-file" => transform_files.push(value(flag)?),
+            "--per-sensor-files" => per_sensor_files = true,
+            "--time-columns" => columns.timestamps = true,
+            "--trace-columns" => columns.trace = true,
+            "--flag-columns" => columns.flag_bits = true,
+            "--drop-flags" => drop_flags = parse_flag_list(&value(flag)?)?,
+            "--polar-columns" => columns.polar = true,
+            "--debug-distance-unit" => columns.distance_unit = parse_distance_unit(&value(flag)?)?,
+            "--split" => split = parse_split(&value(flag)?)?,
+            "-f" | "--format" => format = Some(parse_format(&value(flag)?)?),
+            "--layout" => layout = parse_layout(&value(flag)?)?,
+            "--pcd-encoding" => pcd_encoding = parse_pcd_encoding(&value(flag)?)?,
+            "--ply-encoding" => ply_encoding = parse_ply_encoding(&value(flag)?)?,
+            "--frames" => frames = Some(parse_frames(&value(flag)?)?),
+            "--frame-range" => frame_range = Some(parse_frame_range(&value(flag)?)?),
+            "--frame-files" => frame_files = true,
+            _ if flag.starts_with('-') && flag.len() > 1 => {
+                return Err(ConvertError::Usage(format!("Unknown option: {}", flag)));
+            }
+            _ => {
+                if input.is_some() {
+                    return Err(ConvertError::Usage(format!("Unexpected argument: {}", arg)));
+                }
+                input = Some(arg.clone());
+            }
+        }
+    }
+
+    let input = input.ok_or_else(|| ConvertError::Usage("Missing input file".to_string()))?;
+
+    if frames.is_none() && (frame_range.is_some() || frame_files) {
+        return Err(ConvertError::Usage("--frame-range and --frame-files need --frames".to_string()));
+    }
+    columns.frames = frames.is_some();
+
+    // Bounds are read once the clock they refer to is known
+    filter.window = TimeWindow {
+        base: time_base,
+        start: start.as_deref().map(|s| parse_time_bound(s, time_base)).transpose()?,
+        end: end.as_deref().map(|s| parse_time_bound(s, time_base)).transpose()?,
+    };
+    if let (Some(TimeBound::Nanos(start)), Some(TimeBound::Nanos(end))) = (filter.window.start, filter.window.end)
+        && start > end
+    {
+        return Err(ConvertError::Usage("--start is after --end".to_string()));
+    }
+
+    Ok(Command::Run(Box::new(CliOptions {
+        input,
+        mode,
+        channels,
+        output,
+        overwrite,
+        filter,
+        region,
+        transforms,
+        transform_files,
+        drop_flags,
+        per_sensor_files,
+        columns,
+        split,
+        format,
+        layout,
+        pcd_encoding,
+        ply_encoding,
+        frames,
+        frame_range,
+        frame_files,
+    })))
+}
+
+/// Parse an option value, reporting the option name on failure
+fn parse_value<T: std::str::FromStr>(flag: &str, value: &str) -> Result<T, ConvertError> {
+    value.trim().parse()
+        .map_err(|_| ConvertError::Usage(format!("Invalid value for {}: {}", flag, value)))
+}
+
+/// Parse a parse mode name ("auto", "normal" or "debug"); auto yields None
+pub fn parse_mode(s: &str) -> Result<Option<ParseMode>, ConvertError> {
+    match s.trim().to_lowercase().as_str() {
+        "auto" | "0" => Ok(None),
+        "normal" | "1" => Ok(Some(ParseMode::Normal)),
+        "debug" | "2" => Ok(Some(ParseMode::Debug)),
+        other => Err(ConvertError::Usage(format!(
+            "Invalid mode '{}'. Use: auto | normal | debug",
+            other
+        ))),
+    }
+}
+
+/// Parse a split mode name ("sheets" or "workbooks")
+pub fn parse_split(s: &str) -> Result<SplitMode, ConvertError> {
+    match s.trim().to_lowercase().as_str() {
+        "sheets" => Ok(SplitMode::Sheets),
+        "workbooks" => Ok(SplitMode::Workbooks),
+        other => Err(ConvertError::Usage(format!(
+            "Invalid split mode '{}'. Use: sheets | workbooks",
+            other
+        ))),
+    }
+}
+
+/// Parse an output format name
+pub fn parse_format(s: &str) -> Result<OutputFormat, ConvertError> {
+    match s.trim().to_lowercase().as_str() {
+        "xlsx" | "excel" => Ok(OutputFormat::Xlsx),
+        "csv" => Ok(OutputFormat::Csv),
+        "tsv" => Ok(OutputFormat::Tsv),
+        "pcd" => Ok(OutputFormat::Pcd),
+        "ply" => Ok(OutputFormat::Ply),
+        "las" => Ok(OutputFormat::Las),
+        "parquet" => Ok(OutputFormat::Parquet),
+        "npy" => Ok(OutputFormat::Npy),
+        "npz" => Ok(OutputFormat::Npz),
+        other => Err(ConvertError::Usage(format!(
+            "Invalid format '{}'. Use: xlsx | csv | tsv | pcd | ply | las | parquet | npy | npz",
+            other
+        ))),
+    }
+}
+
+/// Parse a file layout name ("per-channel" or "merged")
+pub fn parse_layout(s: &str) -> Result<Layout, ConvertError> {
+    match s.trim().to_lowercase().as_str() {
+        "per-channel" => Ok(Layout::PerChannel),
+        "merged" => Ok(Layout::Merged),
+        other => Err(ConvertError::Usage(format!(
+            "Invalid layout '{}'. Use: per-channel | merged",
+            other
+        ))),
+    }
+}
+
+/// Parse a PCD data encoding name
+pub fn parse_pcd_encoding(s: &str) -> Result<PcdEncoding, ConvertError> {
+    match s.trim().to_lowercase().as_str() {
+        "ascii" => Ok(PcdEncoding::Ascii),
+        "binary" => Ok(PcdEncoding::Binary),
+        "binary_compressed" => Ok(PcdEncoding::BinaryCompressed),
+        other => Err(ConvertError::Usage(format!(
+            "Invalid PCD encoding '{}'. Use: ascii | binary | binary_compressed",
+            other
+        ))),
+    }
+}
+
+/// Parse a PLY data encoding name
+pub fn parse_ply_encoding(s: &str) -> Result<PlyEncoding, ConvertError> {
+    match s.trim().to_lowercase().as_str() {
+        "ascii" => Ok(PlyEncoding::Ascii),
+        "binary" | "binary_little_endian" => Ok(PlyEncoding::BinaryLittleEndian),
+        other => Err(ConvertError::Usage(format!(
+            "Invalid PLY encoding '{}'. Use: ascii | binary",
+            other
+        ))),
+    }
+}
+
+/// Parse a Debug distance unit: "mm", "cm", "m" or meters per count
+pub fn parse_distance_unit(s: &str) -> Result<f64, ConvertError> {
+    match s.trim().to_lowercase().as_str() {
+        "mm" => Ok(0.001),
+        "cm" => Ok(0.01),
+        "m" => Ok(1.0),
+        other => match other.parse::<f64>() {
+            Ok(unit) if unit.is_finite() && unit > 0.0 => Ok(unit),
+            _ => Err(ConvertError::Usage(format!(
+                "Invalid distance unit '{}'. Use: mm | cm | m | <meters per count>",
+                other
+            ))),
+        },
+    }
+}
+
+/// Parse a frame mode: "wrap" or a frame period in milliseconds
+pub fn parse_frames(s: &str) -> Result<FrameMode, ConvertError> {
+    let s = s.trim().to_lowercase();
+    if s == "wrap" {
+        return Ok(FrameMode::Wrap);
+    }
+
+    match s.trim_end_matches("ms").trim().parse::<f64>() {
+        Ok(ms) if ms * 1000.0 >= 1.0 => Ok(FrameMode::Period((ms * 1000.0).round() as u64)),
+        _ => Err(ConvertError::Usage(format!(
+            "Invalid frame mode '{}'. Use: wrap | <period in ms>",
+            s
+        ))),
+    }
+}
+
+/// Parse a frame range such as "10-20" or "7"
+pub fn parse_frame_range(s: &str) -> Result<RangeInclusive<u64>, ConvertError> {
+    let invalid = || ConvertError::Usage(format!("Invalid frame range '{}'. Use: N-M or N", s));
+    let number = |n: &str| n.trim().parse::<u64>().map_err(|_| invalid());
+
+    let (first, last) = match s.split_once('-') {
+        Some((first, last)) => (number(first)?, number(last)?),
+        None => (number(s)?, number(s)?),
+    };
+    if first > last {
+        return Err(invalid());
+    }
+
+    Ok(first..=last)
+}
+
+/// Parse a time base name ("offset", "sensor" or "capture")
+pub fn parse_time_base(s: &str) -> Result<TimeBase, ConvertError> {
+    match s.trim().to_lowercase().as_str() {
+        "offset" => Ok(TimeBase::Offset),
+        "sensor" => Ok(TimeBase::Sensor),
+        "capture" => Ok(TimeBase::Capture),
+        other => Err(ConvertError::Usage(format!(
+            "Invalid time base '{}'. Use: offset | sensor | capture",
+            other
+        ))),
+    }
+}
+
+/// Parse a --start / --end time: seconds, or for capture time also a date and time
+pub fn parse_time_bound(s: &str, base: TimeBase) -> Result<TimeBound, ConvertError> {
+    if let Ok(seconds) = s.trim().trim_end_matches('s').parse::<f64>()
+        && seconds >= 0.0
+    {
+        return Ok(TimeBound::Nanos((seconds * 1e9).round() as u64));
+    }
+
+    let datetime = if base == TimeBase::Capture { time_window::parse_datetime(s) } else { None };
+    datetime.ok_or_else(|| {
+        let expected = match base {
+            TimeBase::Capture => "[YYYY-MM-DD ]HH:MM:SS[.fff][Z|+HH:MM] or Unix seconds",
+            TimeBase::Sensor | TimeBase::Offset => "seconds, e.g. 12.5 (dates need --time-base capture)",
+        };
+        ConvertError::Usage(format!("Invalid time '{}'. Use: {}", s, expected))
+    })
+}
+
+/// Parse the value of a --roi-* option: comma separated bounds, each as min,max
+pub fn parse_region(flag: &str, s: &str) -> Result<Region, ConvertError> {
+    let invalid = |reason: &str| ConvertError::Usage(format!("Invalid value for {}: {} ({})", flag, s, reason));
+    let values = s
+        .split(',')
+        .map(|v| v.trim().parse::<f64>().ok().filter(|v| v.is_finite()))
+        .collect::<Option<Vec<f64>>>()
+        .ok_or_else(|| invalid("expected numbers"))?;
+    let pair = |i: usize| (values[i], values[i + 1]);
+
+    let region = match (flag, values.len()) {
+        ("--roi-box", 6) => Region::Box { min: [values[0], values[1], values[2]], max: [values[3], values[4], values[5]] },
+        ("--roi-cylinder", 2) => Region::Cylinder { radius: pair(0), z: (f64::NEG_INFINITY, f64::INFINITY) },
+        ("--roi-cylinder", 4) => Region::Cylinder { radius: pair(0), z: pair(2) },
+        ("--roi-range", 2) => Region::Range { min: values[0], max: values[1] },
+        ("--roi-sector", 2) => Region::Sector { azimuth: pair(0), elevation: (-90.0, 90.0) },
+        ("--roi-sector", 4) => Region::Sector { azimuth: pair(0), elevation: pair(2) },
+        ("--roi-box", _) => return Err(invalid("expected X0,Y0,Z0,X1,Y1,Z1")),
+        ("--roi-cylinder", _) => return Err(invalid("expected R0,R1 or R0,R1,Z0,Z1")),
+        ("--roi-sector", _) => return Err(invalid("expected A0,A1 or A0,A1,E0,E1")),
+        _ => return Err(invalid("expected R0,R1")),
+    };
+
+    // Every lower bound must not exceed its upper bound, except a wrapping azimuth
+    let ordered = match region {
+        Region::Box { min, max } => min.iter().zip(&max).all(|(min, max)| min <= max),
+        Region::Cylinder { radius, z } => radius.0 <= radius.1 && z.0 <= z.1,
+        Region::Range { min, max } => min <= max,
+        Region::Sector { azimuth, elevation } => {
+            let degrees = |(from, to): (f64, f64), limit: f64| from.abs() <= limit && to.abs() <= limit;
+            if !degrees(azimuth, 180.0) || !degrees(elevation, 90.0) {
+                return Err(invalid("azimuth must be within ±180, elevation within ±90"));
+            }
+            elevation.0 <= elevation.1
+        }
+    };
+    if !ordered {
+        return Err(invalid("a lower bound exceeds its upper bound"));
+    }
+
+    Ok(region)
+}
+
+/// Parse the frame of the --roi-* options ("vehicle" or "sensor")
+pub fn parse_roi_frame(s: &str) -> Result<CoordFrame, ConvertError> {
+    match s.trim().to_lowercase().as_str() {
+        "vehicle" => Ok(CoordFrame::Vehicle),
+        "sensor" => Ok(CoordFrame::Sensor),
+        other => Err(ConvertError::Usage(format!(
+            "Invalid region frame '{}'. Use: vehicle | sensor",
+            other
+        ))),
+    }
+}
+
+/// Parse a --transform value: "[SENSOR=]" then a pose (6 numbers) or a 4x4 matrix (16 numbers),
+/// separated by commas or whitespace
+pub fn parse_transform(s: &str) -> Result<(Option<SensorId>, Transform), ConvertError> {
+    let invalid = |reason: &str| ConvertError::Usage(format!("Invalid transform '{}' ({})", s.trim(), reason));
+
+    let (sensor, values) = match s.split_once('=') {
+        Some((sensor, values)) => {
+            let sensor = sensor.trim();
+            let id = match (sensor.parse::<SocketAddr>(), sensor.parse::<IpAddr>()) {
+                (Ok(addr), _) => SensorId::Addr(addr),
+                (_, Ok(ip)) => SensorId::Ip(ip),
+                _ => return Err(invalid("sensor must be an IP or IP:port")),
+            };
+            (Some(id), values)
+        }
+        None => (None, s),
+    };
+
+    let values = values
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|v| !v.is_empty())
+        .map(|v| v.parse::<f64>().ok().filter(|v| v.is_finite()))
+        .collect::<Option<Vec<f64>>>()
+        .ok_or_else(|| invalid("expected numbers"))?;
+
+    let transform = match values.len() {
+        6 => Transform::from_pose([values[0], values[1], values[2]], values[3], values[4], values[5]),
+        16 => Transform::from_matrix(values.try_into().unwrap_or([0.0; 16]))
+            .ok_or_else(|| invalid("the matrix is not a rotation plus translation"))?,
+        _ => return Err(invalid("expected X,Y,Z,ROLL,PITCH,YAW or 16 matrix values")),
+    };
+
+    Ok((sensor, transform))
+}
+
+/// Read a --transform-file: one --transform value per line; blank lines and # comments are skipped
+pub fn parse_transform_file(path: &str) -> anyhow::Result<Vec<(Option<SensorId>, Transform)>> {
+    let text = std::fs::read_to_string(path).with_context(|| format!("Cannot read transform file {}", path))?;
+
+    let transforms = text
+        .lines()
+        .enumerate()
+        .map(|(i, line)| (i, line.split('#').next().unwrap_or("").trim()))
+        .filter(|(_, line)| !line.is_empty())
+        .map(|(i, line)| {
+            parse_transform(line).map_err(|e| ConvertError::Usage(format!("{}, line {}: {}", path, i + 1, e)))
+        })
+        .collect::<Result<_, _>>()?;
+    Ok(transforms)
+}
+
+/// Transforms of every --transform-file in order, then the --transform values on top
+pub fn resolve_transforms(options: &CliOptions) -> anyhow::Result<SensorTransforms> {
+    let mut transforms = SensorTransforms::default();
+    for path in &options.transform_files {
+        for (sensor, transform) in parse_transform_file(path)? {
+            transforms.insert(sensor, transform);
+        }
+    }
+    transforms.extend(&options.transforms);
+    Ok(transforms)
+}
+
+/// Parse a comma separated list of flag names, or "none"
+pub fn parse_flag_list(s: &str) -> Result<FlagMask, ConvertError> {
+    if s.trim().eq_ignore_ascii_case("none") {
+        return Ok(FlagMask::default());
+    }
+
+    let mut mask = FlagMask::default();
+    for name in s.split(',').map(str::trim).filter(|name| !name.is_empty()) {
+        let flag = flags::find_flag(name).ok_or_else(|| {
+            let names: Vec<&str> = flags::POINT_FLAGS.iter().map(|flag| flag.name).collect();
+            ConvertError::Usage(format!("Invalid flag '{}'. Use: {}", name, names.join(" | ")))
+        })?;
+        mask.0 |= flag.mask;
+    }
+
+    Ok(mask)
+}
+
+/// Resolve a channel selection such as "all", "0,5,10" or "0,5,10-20"
+/// against the channels present in the capture
+pub fn parse_channel_spec(spec: &str, available: &[u8]) -> Result<Vec<u8>, ConvertError> {
+    let spec = spec.trim().to_lowercase();
+    if spec == "all" {
+        return Ok(available.to_vec());
+    }
+
+    let mut selected = Vec::new();
+    for part in spec.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+        if let Some((start, end)) = part.split_once('-') {
+            // Range selection (e.g., "10-20")
+            let start: u8 = start.trim().parse()
+                .map_err(|_| ConvertError::Usage(format!("Invalid range start: {}", part)))?;
+            let end: u8 = end.trim().parse()
+                .map_err(|_| ConvertError::Usage(format!("Invalid range end: {}", part)))?;
+            selected.extend((start..=end).filter(|ch| available.contains(ch)));
+        } else {
+            let ch: u8 = part.parse()
+                .map_err(|_| ConvertError::Usage(format!("Invalid channel: {}", part)))?;
+            if available.contains(&ch) {
+                selected.push(ch);
+            }
+        }
+    }
+
+    selected.sort();
+    selected.dedup();
+
+    if selected.is_empty() {
+        return Err(ConvertError::NoChannelsMatched);
+    }
+
+    Ok(selected)
+}
+
+/// Default output path: "<input stem>_xyz.<ext>" next to the input file
+pub fn default_output_path(input: &str, format: OutputFormat) -> String {
+    let path = Path::new(input);
+    let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or("output");
+    path.with_file_name(format!("{}_xyz.{}", stem, format.extension()))
+        .to_string_lossy()
+        .into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_parse_args() {
+        let cmd = parse_args(&args(&[
+            "capture.pcap", "--mode", "debug", "--channels=0,5", "-o", "out.xlsx", "--yes",
+        ]))
+        .unwrap();
+
+        assert_eq!(
+            cmd,
+            Command::Run(Box::new(CliOptions {
+                input: "capture.pcap".to_string(),
+                mode: Some(ParseMode::Debug),
+                channels: "0,5".to_string(),
+                output: Some("out.xlsx".to_string()),
+                overwrite: true,
+                filter: PacketFilter::default(),
+                region: RegionFilter::default(),
+                transforms: SensorTransforms::default(),
+                transform_files: Vec::new(),
+                drop_flags: FlagMask::default(),
+                per_sensor_files: false,
+                columns: ColumnOptions::default(),
+                split: SplitMode::Sheets,
+                format: None,
+                layout: Layout::PerChannel,
+                pcd_encoding: PcdEncoding::Binary,
+                ply_encoding: PlyEncoding::BinaryLittleEndian,
+                frames: None,
+                frame_range: None,
+                frame_files: false,
+            }))
+        );
+    }
+
+    #[test]
+    fn test_parse_args_filter() {
+        let Command::Run(options) = parse_args(&args(&[
+            "capture.pcap", "--src-ip", "192.168.1.201", "--dst-port=8808",
+        ]))
+        .unwrap() else {
+            panic!("expected Run");
+        };
+
+        assert_eq!(options.filter.src_ip, Some("192.168.1.201".parse().unwrap()));
+        assert_eq!(options.filter.src_port, None);
+        assert_eq!(options.filter.dst_port, Some(8808));
+        assert_eq!(options.mode, None);
+        assert!(!options.columns.timestamps);
+        assert_eq!(options.split, SplitMode::Sheets);
+        assert_eq!(options.output_format(), OutputFormat::Xlsx);
+
+        assert!(matches!(parse_args(&args(&["a.pcap", "--src-ip", "nope"])), Err(ConvertError::Usage(_))));
+        assert!(matches!(parse_args(&args(&["a.pcap", "--dst-port", "70000"])), Err(ConvertError::Usage(_))));
+    }
+
+    #[test]
+    fn test_parse_args_format() {
+        let Command::Run(options) = parse_args(&args(&["a.pcap", "-o", "out/a.TSV", "--layout=merged"])).unwrap() else {
+            panic!("expected Run");
+        };
+        assert_eq!(options.output_format(), OutputFormat::Tsv);
+        assert_eq!(options.layout, Layout::Merged);
+
+        let Command::Run(options) = parse_args(&args(&["a.pcap", "-o", "a.txt", "-f", "csv"])).unwrap() else {
+            panic!("expected Run");
+        };
+        assert_eq!(options.output_format(), OutputFormat::Csv);
+
+        assert!(matches!(parse_args(&args(&["a.pcap", "-f", "json"])), Err(ConvertError::Usage(_))));
+        assert!(matches!(parse_args(&args(&["a.pcap", "--layout", "x"])), Err(ConvertError::Usage(_))));
+
+        let Command::Run(options) = parse_args(&args(&["a.pcap", "-f", "pcd", "--pcd-encoding", "binary_compressed"])).unwrap() else {
+            panic!("expected Run");
+        };
+        assert_eq!(options.output_format(), OutputFormat::Pcd);
+        assert_eq!(options.pcd_encoding, PcdEncoding::BinaryCompressed);
+
+        let Command::Run(options) = parse_args(&args(&["a.pcap", "-o", "a.ply", "--ply-encoding=ascii"])).unwrap() else {
+            panic!("expected Run");
+        };
+        assert_eq!(options.output_format(), OutputFormat::Ply);
+        assert_eq!(options.ply_encoding, PlyEncoding::Ascii);
+        assert!(matches!(parse_args(&args(&["a.pcap", "--ply-encoding", "big"])), Err(ConvertError::Usage(_))));
+
+        let Command::Run(options) = parse_args(&args(&["a.pcap", "--format", "LAS"])).unwrap() else {
+            panic!("expected Run");
+        };
+        assert_eq!(options.output_format(), OutputFormat::Las);
+
+        let Command::Run(options) = parse_args(&args(&["a.pcap", "-o", "a.parquet"])).unwrap() else {
+            panic!("expected Run");
+        };
+        assert_eq!(options.output_format(), OutputFormat::Parquet);
+
+        let Command::Run(options) = parse_args(&args(&["a.pcap", "-f", "npz"])).unwrap() else {
+            panic!("expected Run");
+        };
+        assert_eq!(options.output_format(), OutputFormat::Npz);
+    }
+
+    #[test]
+    fn test_parse_args_errors() {
+        assert!(matches!(parse_args(&args(&["--mode", "normal"])), Err(ConvertError::Usage(_))));
+        assert!(matches!(parse_args(&args(&["a.pcap", "--bogus"])), Err(ConvertError::Usage(_))));
+        assert!(matches!(parse_args(&args(&["a.pcap", "-m", "fast"])), Err(ConvertError::Usage(_))));
+        assert!(matches!(parse_args(&args(&["a.pcap", "-c"])), Err(ConvertError::Usage(_))));
+        assert!(matches!(parse_args(&args(&["a.pcap", "--split", "files"])), Err(ConvertError::Usage(_))));
+        assert!(matches!(parse_args(&args(&["a.pcap", "--frame-files"])), Err(ConvertError::Usage(_))));
+    }
+
+    #[test]
+    fn test_parse_frames() {
+        let Command::Run(options) = parse_args(&args(&["a.pcap", "--frames=100", "--frame-range", "3-5"])).unwrap() else {
+            panic!("expected Run");
+        };
+        assert_eq!(options.frames, Some(FrameMode::Period(100_000)));
+        assert_eq!(options.frame_range, Some(3..=5));
+        assert!(options.columns.frames);
+
+        assert_eq!(parse_frames("WRAP").unwrap(), FrameMode::Wrap);
+        assert_eq!(parse_frames("66.7ms").unwrap(), FrameMode::Period(66_700));
+        assert!(parse_frames("0").is_err());
+        assert!(parse_frames("fast").is_err());
+
+        assert_eq!(parse_frame_range("7").unwrap(), 7..=7);
+        assert_eq!(parse_frame_range(" 0 - 12 ").unwrap(), 0..=12);
+        assert!(parse_frame_range("5-2").is_err());
+        assert!(parse_frame_range("-3").is_err());
+    }
+
+    #[test]
+    fn test_parse_time_window() {
+        let Command::Run(options) = parse_args(&args(&["a.pcap", "--start", "2.5", "--end=10s"])).unwrap() else {
+            panic!("expected Run");
+        };
+        assert_eq!(options.filter.window.base, TimeBase::Offset);
+        assert_eq!(options.filter.window.start, Some(TimeBound::Nanos(2_500_000_000)));
+        assert_eq!(options.filter.window.end, Some(TimeBound::Nanos(10_000_000_000)));
+
+        // The base may follow the bounds
+        let Command::Run(options) = parse_args(&args(&["a.pcap", "--start", "14:32:00+08:00", "--time-base", "capture"])).unwrap() else {
+            panic!("expected Run");
+        };
+        assert_eq!(options.filter.window.start, Some(TimeBound::TimeOfDay((6 * 3600 + 32 * 60) * 1_000_000_000)));
+        assert_eq!(options.filter.window.end, None);
+
+        assert!(parse_time_bound("14:32:00", TimeBase::Sensor).is_err());
+        assert!(parse_time_bound("-1", TimeBase::Offset).is_err());
+        assert_eq!(parse_time_bound("1714545125", TimeBase::Capture).unwrap(), TimeBound::Nanos(1_714_545_125_000_000_000));
+        assert!(matches!(parse_args(&args(&["a.pcap", "--start", "5", "--end", "4"])), Err(ConvertError::Usage(_))));
+        assert!(matches!(parse_args(&args(&["a.pcap", "--time-base", "gps"])), Err(ConvertError::Usage(_))));
+    }
+
+    #[test]
+    fn test_parse_region() {
+        let Command::Run(options) = parse_args(&args(&[
+            "a.pcap", "--roi-box", "-1,4,-1,1,6,1", "--roi-sector=-30,30",
+        ]))
+        .unwrap() else {
+            panic!("expected Run");
+        };
+        assert_eq!(
+            options.region.regions,
+            vec![
+                Region::Box { min: [-1.0, 4.0, -1.0], max: [1.0, 6.0, 1.0] },
+                Region::Sector { azimuth: (-30.0, 30.0), elevation: (-90.0, 90.0) },
+            ]
+        );
+        assert_eq!(options.region.frame, CoordFrame::Vehicle);
+        let Command::Run(options) = parse_args(&args(&["a.pcap", "--roi-range=0,5", "--roi-frame", "sensor"])).unwrap()
+        else {
+            panic!("expected Run");
+        };
+        assert_eq!(options.region.frame, CoordFrame::Sensor);
+        assert!(parse_roi_frame("world").is_err());
+
+        assert_eq!(parse_region("--roi-range", "2, 50").unwrap(), Region::Range { min: 2.0, max: 50.0 });
+        assert_eq!(
+            parse_region("--roi-cylinder", "0,10,-2,3").unwrap(),
+            Region::Cylinder { radius: (0.0, 10.0), z: (-2.0, 3.0) }
+        );
+        assert!(parse_region("--roi-sector", "170,-170").is_ok()); // wraps through 180
+        assert!(parse_region("--roi-range", "5,2").is_err());
+        assert!(parse_region("--roi-box", "0,0,0,1,1").is_err());
+        assert!(parse_region("--roi-sector", "0,200").is_err());
+        assert!(parse_region("--roi-cylinder", "0,x").is_err());
+    }
+
+    #[test]
+    fn test_parse_flags() {
+        let Command::Run(options) = parse_args(&args(&["a.pcap", "--flag-columns", "--drop-flags", "no-return, saturated"])).unwrap() else {
+            panic!("expected Run");
+        };
+        assert!(options.columns.flag_bits);
+        assert_eq!(options.drop_flags, FlagMask(0x21));
+
+        assert_eq!(parse_flag_list("none").unwrap(), FlagMask(0));
+        assert!(matches!(parse_flag_list("saturated,dusty"), Err(ConvertError::Usage(_))));
+    }
+
+    #[test]
+    fn test_parse_polar_columns() {
+        let Command::Run(options) = parse_args(&args(&["a.pcap", "--polar-columns", "-f", "las"])).unwrap() else {
+            panic!("expected Run");
+        };
+        assert!(options.columns.polar);
+        assert!(!options.columns.flag_bits);
+        assert_eq!(options.columns.distance_unit, 0.001);
+
+        let Command::Run(options) = parse_args(&args(&["a.pcap", "--debug-distance-unit", "cm"])).unwrap() else {
+            panic!("expected Run");
+        };
+        assert_eq!(options.columns.distance_unit, 0.01);
+        assert_eq!(parse_distance_unit("0.005").unwrap(), 0.005);
+        assert!(matches!(parse_distance_unit("0"), Err(ConvertError::Usage(_))));
+        assert!(matches!(parse_distance_unit("feet"), Err(ConvertError::Usage(_))));
+    }
+
+    #[test]
+    fn test_parse_transform() {
+        let Command::Run(options) =
+            parse_args(&args(&["a.pcap", "--transform", "192.168.1.201=1.2,0,1.8,0,-1.5,90", "--transform=0,0,0,0,0,180"]))
+                .unwrap()
+        else {
+            panic!("expected Run");
+        };
+        let sensor: SocketAddr = "192.168.1.201:8808".parse().unwrap();
+        assert_eq!(options.transforms.get(sensor), Some(&Transform::from_pose([1.2, 0.0, 1.8], 0.0, -1.5, 90.0)));
+        assert_eq!(options.transforms.default, Some(Transform::from_pose([0.0; 3], 0.0, 0.0, 180.0)));
+
+        let (sensor, _) = parse_transform("192.168.1.202:8808 = 1 0 0 5  0 1 0 0  0 0 1 0  0 0 0 1").unwrap();
+        assert_eq!(sensor, Some(SensorId::Addr("192.168.1.202:8808".parse().unwrap())));
+        assert!(parse_transform("2 0 0 0  0 1 0 0  0 0 1 0  0 0 0 1").is_err()); // scaled
+        assert!(parse_transform("1,2,3").is_err());
+        assert!(parse_transform("lidar=0,0,0,0,0,0").is_err());
+
+        let path = std::env::temp_dir().join("test_transforms.txt");
+        std::fs::write(&path, "# vehicle frame\n192.168.1.201 = 1.2,0,1.8, 0,0,90\n\n0,0,0,0,0,0 # others\n").unwrap();
+        let path = path.to_str().unwrap();
+        let Command::Run(options) = parse_args(&args(&["a.pcap", "--transform=0,0,0,0,0,180", "--transform-file", path]))
+            .unwrap()
+        else {
+            panic!("expected Run");
+        };
+        assert_eq!(options.transform_files, [path]);
+        let transforms = resolve_transforms(&options).unwrap();
+        std::fs::write(path, "0,0,0\n").unwrap();
+        let error = parse_transform_file(path).unwrap_err();
+        let _ = std::fs::remove_file(path);
+        assert_eq!(transforms.get("192.168.1.201:8808".parse().unwrap()), Some(&Transform::from_pose([1.2, 0.0, 1.8], 0.0, 0.0, 90.0)));
+        assert_eq!(transforms.default, Some(Transform::from_pose([0.0; 3], 0.0, 0.0, 180.0))); // option beats file
+        assert!(error.to_string().contains("line 1"));
+        assert_eq!(crate::error::exit_code_for(&error), crate::error::exit_code::USAGE);
+
+        let missing = parse_transform_file("/nonexistent/rig.txt").unwrap_err();
+        assert_eq!(crate::error::exit_code_for(&missing), crate::error::exit_code::IO);
+    }
+
+    #[test]
+    fn test_parse_channel_spec() {
+        let available = [0, 1, 2, 5, 10, 11, 12, 30];
+
+        assert_eq!(parse_channel_spec("all", &available).unwrap(), available.to_vec());
+        assert_eq!(parse_channel_spec("0,5,10-20", &available).unwrap(), vec![0, 5, 10, 11, 12]);
+        assert_eq!(parse_channel_spec(" 12, 0-1 ,5 ", &available).unwrap(), vec![0, 1, 5, 12]);
+        assert!(matches!(parse_channel_spec("40-50", &available), Err(ConvertError::NoChannelsMatched)));
+        assert!(matches!(parse_channel_spec("x", &available), Err(ConvertError::Usage(_))));
+    }
+
+    #[test]
+    fn test_default_output_path() {
+        assert_eq!(default_output_path("ch_28 (1).pcap", OutputFormat::Xlsx), "ch_28 (1)_xyz.xlsx");
+        assert_eq!(default_output_path("data/run.pcapng", OutputFormat::Csv), "data/run_xyz.csv");
+    }
+}
@@ -0,0 +1,217 @@
+//! Output column layout shared by the exporters
+
+use crate::cepton::Point;
+use crate::flags::POINT_FLAGS;
+
+/// Meters per count of the Debug mode distance field unless --debug-distance-unit says otherwise
+pub const DEFAULT_DISTANCE_UNIT: f64 = 0.001;
+
+/// Optional column groups selected by the user
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ColumnOptions {
+    pub timestamps: bool,   // sensor time and capture time
+    pub trace: bool,        // channel, sequence ID, packet and point index
+    pub frames: bool,       // scan frame index
+    pub flag_bits: bool,    // one 0/1 column per flag bit
+    pub polar: bool,        // range, azimuth and elevation
+    pub distance_unit: f64, // meters per Debug distance count, for the range comparison
+}
+
+impl Default for ColumnOptions {
+    fn default() -> Self {
+        ColumnOptions {
+            timestamps: false,
+            trace: false,
+            frames: false,
+            flag_bits: false,
+            polar: false,
+            distance_unit: DEFAULT_DISTANCE_UNIT,
+        }
+    }
+}
+
+/// One output column
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Column {
+    X,
+    Y,
+    Z,
+    Reflectivity,
+    Flags,
+    FlagBit(u8), // index into POINT_FLAGS
+    Distance,
+    Intensity,
+    PowerLevel,
+    Range,
+    Azimuth,
+    Elevation,
+    RangeError(f64), // computed range - Debug distance; meters per distance count
+    SensorTime,
+    CaptureTime,
+    Channel,
+    SequenceId,
+    PacketIndex,
+    PointIndex,
+    Frame,
+}
+
+impl Column {
+    /// Column header text
+    pub fn header(self) -> &'static str {
+        match self {
+            Column::X => "X (m)",
+            Column::Y => "Y (m)",
+            Column::Z => "Z (m)",
+            Column::Reflectivity => "Reflectivity",
+            Column::Flags => "Flags",
+            Column::FlagBit(bit) => POINT_FLAGS[bit as usize].header,
+            Column::Distance => "Distance",
+            Column::Intensity => "Intensity",
+            Column::PowerLevel => "Power Level",
+            Column::Range => "Range (m)",
+            Column::Azimuth => "Azimuth (deg)",
+            Column::Elevation => "Elevation (deg)",
+            Column::RangeError(_) => "Range - Distance (m)",
+            Column::SensorTime => "Sensor Time (us)",
+            Column::CaptureTime => "Capture Time (s)",
+            Column::Channel => "Channel",
+            Column::SequenceId => "Sequence ID",
+            Column::PacketIndex => "Packet No.",
+            Column::PointIndex => "Point Index",
+            Column::Frame => "Frame",
+        }
+    }
+
+    /// Column value for a point; None leaves the cell empty
+    pub fn value(self, point: &Point) -> Option<f64> {
+        match self {
+            Column::X => Some(point.x),
+            Column::Y => Some(point.y),
+            Column::Z => Some(point.z),
+            Column::Reflectivity => Some(point.reflectivity as f64),
+            Column::Flags => Some(point.flags as f64),
+            Column::FlagBit(bit) => Some((point.flags & POINT_FLAGS[bit as usize].mask != 0) as u8 as f64),
+            Column::Distance => point.distance.map(|v| v as f64),
+            Column::Intensity => point.intensity.map(|v| v as f64),
+            Column::PowerLevel => point.power_level.map(|v| v as f64),
+            Column::Range => Some(point.range()),
+            Column::Azimuth => Some(point.azimuth()),
+            Column::Elevation => Some(point.elevation()),
+            Column::RangeError(unit) => point.range_error(unit),
+            Column::SensorTime => Some(point.timestamp_us as f64),
+            Column::CaptureTime => {
+                // Split before converting so sub-second digits survive the f64 conversion
+                let secs = point.capture_time_ns / 1_000_000_000;
+                let nanos = point.capture_time_ns % 1_000_000_000;
+                Some(secs as f64 + nanos as f64 / 1e9)
+            }
+            Column::Channel => Some(point.channel as f64),
+            Column::SequenceId => Some(point.sequence_id as f64),
+            Column::PacketIndex => Some(point.packet_index as f64),
+            Column::PointIndex => Some(point.point_index as f64),
+            Column::Frame => Some(point.frame as f64),
+        }
+    }
+
+    /// Column value as text, with the decimals the Excel export displays;
+    /// None leaves the field empty
+    pub fn text(self, point: &Point) -> Option<String> {
+        let value = self.value(point)?;
+        Some(match self {
+            Column::X | Column::Y | Column::Z | Column::Range | Column::RangeError(_) => format!("{:.4}", value),
+            Column::Azimuth | Column::Elevation => format!("{:.3}", value),
+            Column::CaptureTime => {
+                let secs = point.capture_time_ns / 1_000_000_000;
+                let nanos = point.capture_time_ns % 1_000_000_000;
+                format!("{}.{:06}", secs, nanos / 1000)
+            }
+            _ => format!("{}", value),
+        })
+    }
+}
+
+/// Columns to write, in order, for points with or without debug fields
+pub fn columns(options: &ColumnOptions, has_debug_data: bool) -> Vec<Column> {
+    let mut columns = vec![Column::X, Column::Y, Column::Z, Column::Reflectivity, Column::Flags];
+
+    if options.flag_bits {
+        columns.extend((0..POINT_FLAGS.len() as u8).map(Column::FlagBit));
+    }
+
+    if has_debug_data {
+        columns.extend([Column::Distance, Column::Intensity, Column::PowerLevel]);
+    }
+
+    if options.polar {
+        columns.extend([Column::Range, Column::Azimuth, Column::Elevation]);
+        if has_debug_data {
+            columns.push(Column::RangeError(options.distance_unit));
+        }
+    }
+
+    if options.timestamps {
+        columns.extend([Column::SensorTime, Column::CaptureTime]);
+    }
+
+    if options.trace {
+        columns.extend([Column::Channel, Column::SequenceId, Column::PacketIndex, Column::PointIndex]);
+    }
+
+    if options.frames {
+        columns.push(Column::Frame);
+    }
+
+    columns
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_columns() {
+        let normal = columns(&ColumnOptions::default(), false);
+        assert_eq!(normal.len(), 5);
+
+        let all = columns(
+            &ColumnOptions {
+                timestamps: true,
+                trace: true,
+                frames: true,
+                flag_bits: true,
+                polar: true,
+                distance_unit: 0.01,
+            },
+            true,
+        );
+        assert_eq!(all.len(), 27);
+        assert_eq!(all[5], Column::FlagBit(0));
+        assert_eq!(all[13], Column::Distance);
+        assert_eq!(all[16], Column::Range);
+        assert_eq!(all[19], Column::RangeError(0.01));
+        assert_eq!(all[21], Column::CaptureTime);
+        assert_eq!(all[25], Column::PointIndex);
+        assert_eq!(all[26], Column::Frame);
+
+        let polar = columns(&ColumnOptions { polar: true, ..Default::default() }, false);
+        assert_eq!(polar[5..], [Column::Range, Column::Azimuth, Column::Elevation]);
+        assert_eq!(Column::Azimuth.text(&Point { x: 1.0, y: 1.0, ..Default::default() }).as_deref(), Some("45.000"));
+
+        let point = Point {
+            timestamp_us: 1_000_123,
+            capture_time_ns: 1_700_000_000_250_000_000,
+            ..Default::default()
+        };
+        assert_eq!(Column::SensorTime.value(&point), Some(1_000_123.0));
+        assert_eq!(Column::CaptureTime.value(&point), Some(1_700_000_000.25));
+        assert_eq!(Column::Distance.value(&point), None);
+        assert_eq!(Column::CaptureTime.text(&point).as_deref(), Some("1700000000.250000"));
+        assert_eq!(Column::SensorTime.text(&point).as_deref(), Some("1000123"));
+        assert_eq!(Column::X.text(&Point { x: 1.005, ..Default::default() }).as_deref(), Some("1.0050"));
+
+        let flagged = Point { flags: 0x21, ..Default::default() };
+        assert_eq!(Column::FlagBit(5).header(), "No Return");
+        assert_eq!(Column::FlagBit(5).text(&flagged).as_deref(), Some("1"));
+        assert_eq!(Column::FlagBit(1).value(&flagged), Some(0.0));
+    }
+}
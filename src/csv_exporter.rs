@@ -0,0 +1,161 @@
+//! CSV / TSV export - plain text files with the same columns as the Excel sheets.
+//! Rows are written as points arrive, so file size is only limited by the disk.
+
+use crate::cepton::{ChannelKey, Point};
+use crate::columns::{self, Column, ColumnOptions};
+use crate::export::{ExportSummary, Layout, OpenFiles, OutputFiles, PointWriter};
+use crate::pcap_reader::SensorStats;
+use anyhow::{Context, Result};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::net::SocketAddr;
+
+/// CSV / TSV writer options
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CsvOptions {
+    pub columns: ColumnOptions,
+    pub layout: Layout,
+    pub per_sensor_files: bool,
+    pub tab_separated: bool,
+}
+
+/// Streams points into one delimited text file per channel, or into one merged file
+pub struct CsvWriter {
+    options: CsvOptions,
+    outputs: OpenFiles<CsvFile>,
+}
+
+/// One open output file
+struct CsvFile {
+    writer: BufWriter<File>,
+    sensor_column: bool,
+    columns: Vec<Column>,
+}
+
+impl CsvWriter {
+    /// Start an export to `output_path`; `sensors` are the sensors whose points will be written
+    pub fn new(output_path: &str, options: &CsvOptions, sensors: &BTreeMap<SocketAddr, SensorStats>) -> Self {
+        let files = OutputFiles::for_sensors(output_path, options.layout, options.per_sensor_files, sensors);
+        CsvWriter {
+            options: *options,
+            outputs: OpenFiles::new(files),
+        }
+    }
+}
+
+/// Field separator
+fn delimiter(options: &CsvOptions) -> &'static str {
+    if options.tab_separated { "\t" } else { "," }
+}
+
+/// Create a file and write its header row; `debug` adds the Debug mode columns
+fn open(path: &str, options: &CsvOptions, sensor_column: bool, debug: bool) -> Result<CsvFile> {
+    let mut columns = columns::columns(&options.columns, debug);
+
+    // A merged file needs the channel to tell its rows apart
+    if options.layout == Layout::Merged && !columns.contains(&Column::Channel) {
+        columns.insert(0, Column::Channel);
+    }
+
+    let file = File::create(path).with_context(|| format!("Failed to create file: {}", path))?;
+    let mut writer = BufWriter::new(file);
+
+    let mut headers: Vec<&str> = Vec::new();
+    if sensor_column {
+        headers.push("Sensor");
+    }
+    headers.extend(columns.iter().map(|column| column.header()));
+    writeln!(writer, "{}", headers.join(delimiter(options)))?;
+
+    Ok(CsvFile { writer, sensor_column, columns })
+}
+
+impl PointWriter for CsvWriter {
+    fn write_point(&mut self, key: ChannelKey, point: &Point) -> Result<()> {
+        let options = &self.options;
+        let sensor_column = self.outputs.files().mixes_sensors();
+        let debug = self.outputs.files().has_debug(key);
+        let file = self.outputs.get_or_open(key, |path| open(path, options, sensor_column, debug))?;
+        let output = &mut file.output;
+
+        let mut fields: Vec<String> = Vec::with_capacity(output.columns.len() + 1);
+        if output.sensor_column {
+            fields.push(key.sensor.to_string());
+        }
+        fields.extend(output.columns.iter().map(|column| column.text(point).unwrap_or_default()));
+
+        writeln!(output.writer, "{}", fields.join(delimiter(options)))
+            .with_context(|| format!("Failed to write file: {}", file.path))
+    }
+
+    fn finish(self: Box<Self>) -> Result<ExportSummary> {
+        let mut summary = ExportSummary::default();
+        for mut file in self.outputs.into_sorted() {
+            file.output.writer
+                .flush()
+                .with_context(|| format!("Failed to write file: {}", file.path))?;
+            summary.files.push(file.path);
+        }
+
+        Ok(summary)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_csv_export() {
+        let a: SocketAddr = "192.168.1.201:8808".parse().unwrap();
+        let b: SocketAddr = "192.168.1.202:8808".parse().unwrap();
+        let point = Point { x: 1.5, y: -2.0, z: 0.25, reflectivity: 40, channel: 3, ..Default::default() };
+
+        // Merged file with sensor and channel columns
+        let options = CsvOptions { layout: Layout::Merged, ..Default::default() };
+        let sensors = BTreeMap::from([(a, SensorStats::default()), (b, SensorStats::default())]);
+        let mut writer = Box::new(CsvWriter::new("test_merged.csv", &options, &sensors));
+        writer.write_point(ChannelKey { sensor: b, channel: 3 }, &point).unwrap();
+        writer.write_point(ChannelKey { sensor: a, channel: 3 }, &point).unwrap();
+        let summary = writer.finish().unwrap();
+
+        assert_eq!(summary.files, vec!["test_merged.csv"]);
+        let text = std::fs::read_to_string("test_merged.csv").unwrap();
+        let _ = std::fs::remove_file("test_merged.csv");
+        let lines: Vec<_> = text.lines().collect();
+        assert_eq!(lines[0], "Sensor,Channel,X (m),Y (m),Z (m),Reflectivity,Flags");
+        assert_eq!(lines[1], "192.168.1.202:8808,3,1.5000,-2.0000,0.2500,40,0");
+        assert_eq!(lines.len(), 3);
+
+        // One TSV file per channel
+        let options = CsvOptions { tab_separated: true, ..Default::default() };
+        let sensors = BTreeMap::from([(a, SensorStats::default())]);
+        let mut writer = Box::new(CsvWriter::new("test_split.tsv", &options, &sensors));
+        writer.write_point(ChannelKey { sensor: a, channel: 7 }, &point).unwrap();
+        writer.write_point(ChannelKey { sensor: a, channel: 2 }, &point).unwrap();
+        let summary = writer.finish().unwrap();
+
+        assert_eq!(summary.files, vec!["test_split_Channel_2.tsv", "test_split_Channel_7.tsv"]);
+        let text = std::fs::read_to_string("test_split_Channel_2.tsv").unwrap();
+        for file in &summary.files {
+            let _ = std::fs::remove_file(file);
+        }
+        assert_eq!(text, "X (m)\tY (m)\tZ (m)\tReflectivity\tFlags\n1.5000\t-2.0000\t0.2500\t40\t0\n");
+
+        // A sensor the scan saw sending Debug packets gets the debug columns from the start,
+        // even when its first point has no debug data
+        let sensors = BTreeMap::from([(a, SensorStats { debug: true, ..Default::default() })]);
+        let mut writer = Box::new(CsvWriter::new("test_debug.csv", &CsvOptions::default(), &sensors));
+        let debug = Point { distance: Some(1234), intensity: Some(55), power_level: Some(3), ..point.clone() };
+        writer.write_point(ChannelKey { sensor: a, channel: 3 }, &point).unwrap();
+        writer.write_point(ChannelKey { sensor: a, channel: 3 }, &debug).unwrap();
+        let summary = writer.finish().unwrap();
+        let text = std::fs::read_to_string(&summary.files[0]).unwrap();
+        let _ = std::fs::remove_file(&summary.files[0]);
+        let lines: Vec<_> = text.lines().collect();
+        assert_eq!(lines[0], "X (m),Y (m),Z (m),Reflectivity,Flags,Distance,Intensity,Power Level");
+        assert_eq!(lines[1], "1.5000,-2.0000,0.2500,40,0,,,");
+        assert_eq!(lines[2], "1.5000,-2.0000,0.2500,40,0,1234,55,3");
+    }
+}
@@ -0,0 +1,189 @@
+//! Packet sequence tracking - gaps, duplicates, reordering and sensor restarts
+
+use std::collections::{HashSet, VecDeque};
+
+/// How many recent sequence IDs are remembered for duplicate / reorder detection
+const HISTORY_LEN: usize = 4096;
+
+/// A backward jump larger than this is treated as a sensor restart, not reordering
+const RESET_THRESHOLD: u64 = HISTORY_LEN as u64;
+
+/// Sequence statistics of one sensor stream
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SequenceStats {
+    pub received: u64,     // unique packets received
+    pub expected: u64,     // packets the sequence IDs say should have arrived
+    pub lost: u64,         // expected - received
+    pub gaps: u64,         // forward jumps in the sequence
+    pub duplicates: u64,   // packets whose sequence ID was already seen
+    pub out_of_order: u64, // packets arriving after a higher sequence ID
+    pub resets: u64,       // large backward jumps (sensor restart)
+    pub first_seq: Option<u32>,
+    pub last_seq: Option<u32>,
+}
+
+impl SequenceStats {
+    /// Lost packets as a fraction of expected packets
+    pub fn loss_ratio(&self) -> f64 {
+        if self.expected == 0 {
+            0.0
+        } else {
+            self.lost as f64 / self.expected as f64
+        }
+    }
+}
+
+/// Tracks the STDV sequence IDs of one sensor.
+/// 32-bit IDs are unwrapped to 64 bits so wraparound is not mistaken for a gap.
+#[derive(Debug, Default)]
+pub struct SequenceTracker {
+    stats: SequenceStats,
+    segment_start: u64,   // first extended ID of the current segment (since last reset)
+    highest: u64,         // highest extended ID of the current segment
+    expected_before: u64, // expected packets of earlier segments
+    received_before: u64, // unique packets of earlier segments
+    received: u64,        // unique packets of the current segment
+    recent: HashSet<u64>,
+    recent_order: VecDeque<u64>,
+}
+
+impl SequenceTracker {
+    /// Record one packet's sequence ID
+    pub fn push(&mut self, seq: u32) {
+        if self.stats.first_seq.is_none() {
+            self.stats.first_seq = Some(seq);
+            self.start_segment(seq as u64);
+            return;
+        }
+        self.stats.last_seq = Some(seq);
+
+        let extended = self.unwrap(seq);
+
+        if self.recent.contains(&extended) {
+            self.stats.duplicates += 1;
+        } else if extended > self.highest {
+            if extended > self.highest + 1 {
+                self.stats.gaps += 1;
+            }
+            self.highest = extended;
+            self.remember(extended);
+        } else if self.highest - extended > RESET_THRESHOLD {
+            // Far behind everything we have seen: the sensor restarted its counter
+            self.stats.resets += 1;
+            self.expected_before += self.highest - self.segment_start + 1;
+            self.received_before += self.received;
+            self.recent.clear();
+            self.recent_order.clear();
+            self.start_segment(seq as u64);
+        } else {
+            // Late arrival filling an earlier gap (or preceding the first packet seen)
+            self.stats.out_of_order += 1;
+            self.segment_start = self.segment_start.min(extended);
+            self.remember(extended);
+        }
+    }
+
+    /// Final statistics
+    pub fn stats(&self) -> SequenceStats {
+        let mut stats = self.stats.clone();
+        if stats.first_seq.is_some() {
+            stats.last_seq = stats.last_seq.or(stats.first_seq);
+            stats.received = self.received_before + self.received;
+            stats.expected = self.expected_before + (self.highest - self.segment_start + 1);
+            stats.lost = stats.expected.saturating_sub(stats.received);
+        }
+        stats
+    }
+
+    fn start_segment(&mut self, seq: u64) {
+        self.segment_start = seq;
+        self.highest = seq;
+        self.received = 0;
+        self.remember(seq);
+    }
+
+    fn remember(&mut self, extended: u64) {
+        self.received += 1;
+        self.recent.insert(extended);
+        self.recent_order.push_back(extended);
+        if self.recent_order.len() > HISTORY_LEN
+            && let Some(old) = self.recent_order.pop_front()
+        {
+            self.recent.remove(&old);
+        }
+    }
+
+    /// Map a 32-bit ID to the 64-bit value closest to the highest ID seen so far
+    fn unwrap(&self, seq: u32) -> u64 {
+        const SPAN: i128 = 1 << 32;
+        let highest = self.highest as i128;
+        let base = highest & !(SPAN - 1);
+
+        [base - SPAN, base, base + SPAN]
+            .into_iter()
+            .map(|b| b + seq as i128)
+            .filter(|&candidate| candidate >= 0)
+            .min_by_key(|&candidate| (candidate - highest).abs())
+            .unwrap_or(seq as i128) as u64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn track(seqs: &[u32]) -> SequenceStats {
+        let mut tracker = SequenceTracker::default();
+        for &seq in seqs {
+            tracker.push(seq);
+        }
+        tracker.stats()
+    }
+
+    #[test]
+    fn test_clean_stream() {
+        let stats = track(&[5, 6, 7, 8]);
+        assert_eq!(stats.received, 4);
+        assert_eq!(stats.expected, 4);
+        assert_eq!(stats.lost, 0);
+        assert_eq!(stats.first_seq, Some(5));
+        assert_eq!(stats.last_seq, Some(8));
+    }
+
+    #[test]
+    fn test_gap_duplicate_and_reorder() {
+        let stats = track(&[1, 2, 5, 5, 3, 6]);
+        assert_eq!(stats.gaps, 1);
+        assert_eq!(stats.duplicates, 1);
+        assert_eq!(stats.out_of_order, 1);
+        assert_eq!(stats.expected, 6);
+        assert_eq!(stats.received, 5);
+        assert_eq!(stats.lost, 1); // only 4 is missing
+
+        // Late packet older than the first one seen
+        let stats = track(&[10, 11, 9, 12]);
+        assert_eq!(stats.out_of_order, 1);
+        assert_eq!(stats.expected, 4);
+        assert_eq!(stats.lost, 0);
+    }
+
+    #[test]
+    fn test_wraparound() {
+        let stats = track(&[u32::MAX - 1, u32::MAX, 0, 2]);
+        assert_eq!(stats.expected, 5);
+        assert_eq!(stats.received, 4);
+        assert_eq!(stats.lost, 1);
+        assert_eq!(stats.gaps, 1);
+        assert_eq!(stats.resets, 0);
+    }
+
+    #[test]
+    fn test_sensor_restart() {
+        let stats = track(&[100_000, 100_001, 100_002, 0, 1, 3]);
+        assert_eq!(stats.resets, 1);
+        assert_eq!(stats.expected, 3 + 4);
+        assert_eq!(stats.received, 6);
+        assert_eq!(stats.lost, 1);
+        assert!((stats.loss_ratio() - 1.0 / 7.0).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,71 @@
+//! Error types and process exit codes
+
+use thiserror::Error;
+
+/// Failures that map to a dedicated process exit code
+#[derive(Debug, Error)]
+pub enum ConvertError {
+    #[error("{0}")]
+    Usage(String),
+
+    #[error("No valid STDV packets found in the file")]
+    NoStdvPackets,
+
+    #[error("No valid channels selected")]
+    NoChannelsMatched,
+}
+
+/// Process exit codes
+pub mod exit_code {
+    pub const FAILURE: u8 = 1;
+    pub const USAGE: u8 = 2;
+    pub const NO_STDV_PACKETS: u8 = 3;
+    pub const NO_CHANNELS_MATCHED: u8 = 4;
+    pub const IO: u8 = 5;
+}
+
+impl ConvertError {
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            ConvertError::Usage(_) => exit_code::USAGE,
+            ConvertError::NoStdvPackets => exit_code::NO_STDV_PACKETS,
+            ConvertError::NoChannelsMatched => exit_code::NO_CHANNELS_MATCHED,
+        }
+    }
+}
+
+/// Pick the exit code for an error by walking its cause chain
+pub fn exit_code_for(err: &anyhow::Error) -> u8 {
+    for cause in err.chain() {
+        if let Some(e) = cause.downcast_ref::<ConvertError>() {
+            return e.exit_code();
+        }
+        if cause.is::<std::io::Error>() {
+            return exit_code::IO;
+        }
+        if let Some(rust_xlsxwriter::XlsxError::IoError(_)) = cause.downcast_ref() {
+            return exit_code::IO;
+        }
+    }
+    exit_code::FAILURE
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Context;
+
+    #[test]
+    fn test_exit_code_for() {
+        let err = anyhow::Error::from(ConvertError::NoStdvPackets);
+        assert_eq!(exit_code_for(&err), exit_code::NO_STDV_PACKETS);
+
+        let err = Err::<(), _>(std::io::Error::from(std::io::ErrorKind::NotFound))
+            .context("Failed to open file")
+            .unwrap_err();
+        assert_eq!(exit_code_for(&err), exit_code::IO);
+
+        let err = anyhow::anyhow!("something else");
+        assert_eq!(exit_code_for(&err), exit_code::FAILURE);
+    }
+}
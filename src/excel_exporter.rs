@@ -1,115 +1,618 @@
-/// Excel export functionality - creates multiple sheets for different channels
+//! Excel export functionality - creates multiple sheets for different channels.
+//! Points are streamed into constant-memory worksheets, so peak memory does not
+//! grow with the number of points.
 
-use crate::cepton::Point;
+use crate::cepton::{ChannelKey, Point};
+use crate::columns::{self, Column, ColumnOptions};
+use crate::export::{
+    ExportSummary, MAX_SHEET_NAME_LEN, PointWriter, SourceInfo, SplitSheet, sensor_labels,
+    sensor_output_path,
+};
+use crate::pcap_reader::SensorStats;
 use anyhow::{Context, Result};
-use rust_xlsxwriter::{Format, Workbook};
-use std::collections::HashMap;
+use rust_xlsxwriter::{Format, Url, Workbook, Worksheet};
+use std::collections::{BTreeMap, HashMap};
+use std::net::SocketAddr;
 
-/// Export channel points to Excel with multiple sheets (one per channel)
-pub fn export_to_excel(channel_points: &HashMap<u8, Vec<Point>>, output_path: &str) -> Result<()> {
-    let mut workbook = Workbook::new();
+/// Excel's worksheet row limit (including the header row)
+pub const EXCEL_MAX_ROWS: usize = 1_048_576;
 
-    // Create format for headers
-    let header_format = Format::new()
-        .set_bold()
-        .set_background_color(rust_xlsxwriter::Color::RGB(0x4472C4))
-        .set_font_color(rust_xlsxwriter::Color::White);
+/// Where a channel continues once it exceeds the row limit
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum SplitMode {
+    #[default]
+    Sheets,    // continuation sheets in the same workbook (Channel_5_part2, ...)
+    Workbooks, // continuation sheets in additional workbooks (out_part2.xlsx, ...)
+}
 
-    // Create format for numbers (4 decimal places)
-    let number_format = Format::new().set_num_format("0.0000");
+/// Excel writer options
+#[derive(Debug, Clone, Copy)]
+pub struct ExcelOptions {
+    pub columns: ColumnOptions,
+    pub split: SplitMode,
+    pub max_rows: usize, // rows per sheet including the header
+    pub per_sensor_files: bool,
+}
 
-    // Sort channels for consistent ordering
-    let mut channels: Vec<_> = channel_points.keys().collect();
-    channels.sort();
+impl Default for ExcelOptions {
+    fn default() -> Self {
+        ExcelOptions {
+            columns: ColumnOptions::default(),
+            split: SplitMode::default(),
+            max_rows: EXCEL_MAX_ROWS,
+            per_sensor_files: false,
+        }
+    }
+}
 
-    for &channel in channels {
-        let points = &channel_points[&channel];
+/// Min / max / mean of one value over a channel
+#[derive(Debug, Clone, Copy, Default)]
+struct ValueStats {
+    min: f64,
+    max: f64,
+    sum: f64,
+    count: u64,
+}
 
-        if points.is_empty() {
-            continue;
+impl ValueStats {
+    fn push(&mut self, value: f64) {
+        if self.count == 0 {
+            self.min = value;
+            self.max = value;
+        } else {
+            self.min = self.min.min(value);
+            self.max = self.max.max(value);
         }
+        self.sum += value;
+        self.count += 1;
+    }
 
-        // Create worksheet for this channel
-        let sheet_name = format!("Channel_{}", channel);
-        let worksheet = workbook.add_worksheet();
-        worksheet.set_name(&sheet_name)?;
-
-        // Check if we have debug data
-        let has_debug_data = points.first().and_then(|p| p.distance).is_some();
-
-        // Write headers
-        let mut col = 0;
-        worksheet.write_with_format(0, col, "X (m)", &header_format)?;
-        col += 1;
-        worksheet.write_with_format(0, col, "Y (m)", &header_format)?;
-        col += 1;
-        worksheet.write_with_format(0, col, "Z (m)", &header_format)?;
-        col += 1;
-        worksheet.write_with_format(0, col, "Reflectivity", &header_format)?;
-        col += 1;
-        worksheet.write_with_format(0, col, "Flags", &header_format)?;
-        col += 1;
-
-        if has_debug_data {
-            worksheet.write_with_format(0, col, "Distance", &header_format)?;
-            col += 1;
-            worksheet.write_with_format(0, col, "Intensity", &header_format)?;
-            col += 1;
-            worksheet.write_with_format(0, col, "Power Level", &header_format)?;
-        }
-
-        // Set column widths
-        worksheet.set_column_width(0, 12)?;
-        worksheet.set_column_width(1, 12)?;
-        worksheet.set_column_width(2, 12)?;
-        worksheet.set_column_width(3, 14)?;
-        worksheet.set_column_width(4, 10)?;
-        if has_debug_data {
-            worksheet.set_column_width(5, 12)?;
-            worksheet.set_column_width(6, 12)?;
-            worksheet.set_column_width(7, 12)?;
-        }
-
-        // Write data
-        for (i, point) in points.iter().enumerate() {
-            let row = (i + 1) as u32;
-            let mut col = 0;
-
-            worksheet.write_with_format(row, col, point.x, &number_format)?;
-            col += 1;
-            worksheet.write_with_format(row, col, point.y, &number_format)?;
-            col += 1;
-            worksheet.write_with_format(row, col, point.z, &number_format)?;
-            col += 1;
-            worksheet.write_number(row, col, point.reflectivity as f64)?;
-            col += 1;
-            worksheet.write_number(row, col, point.flags as f64)?;
-            col += 1;
-
-            // Write debug fields if present
-            if has_debug_data {
-                if let Some(distance) = point.distance {
-                    worksheet.write_number(row, col, distance as f64)?;
-                }
-                col += 1;
-                if let Some(intensity) = point.intensity {
-                    worksheet.write_number(row, col, intensity as f64)?;
-                }
-                col += 1;
-                if let Some(power_level) = point.power_level {
-                    worksheet.write_number(row, col, power_level as f64)?;
-                }
-            }
+    fn mean(&self) -> f64 {
+        if self.count == 0 { 0.0 } else { self.sum / self.count as f64 }
+    }
+}
+
+/// Values summarized per channel on the Summary sheet
+const SUMMARY_VALUES: [&str; 5] = ["X", "Y", "Z", "Sensor Range", "Reflectivity"];
+
+/// Summary sheet row of one channel
+struct ChannelSummary {
+    key: ChannelKey,
+    sheet: String,
+    points: usize,
+    parts: usize,
+    values: [ValueStats; 5], // in SUMMARY_VALUES order
+}
+
+/// Streams points into Excel, one sheet per channel.
+/// Captures with several sensors get the sensor label as sheet name prefix, or
+/// one workbook per sensor with `per_sensor_files`.
+/// Each workbook starts with a "Summary" sheet and ends with a "Diagnostics"
+/// sheet with the sequence integrity of `sensors`.
+pub struct ExcelWriter {
+    options: ExcelOptions,
+    source: SourceInfo,
+    output_path: String,
+    labels: BTreeMap<SocketAddr, String>,
+    sensors: BTreeMap<SocketAddr, SensorStats>,
+    books: Vec<Book>,
+    sheets: HashMap<ChannelKey, Sheet>,
+    formats: Formats,
+}
+
+/// Cell formats shared by all sheets
+struct Formats {
+    header: Format,
+    label: Format,
+    number: Format, // 4 decimal places
+    angle: Format,  // degrees, 3 decimal places
+    time: Format,   // Unix seconds with microsecond digits
+}
+
+/// One output workbook and the channel sheets finished so far
+struct Book {
+    path: String,
+    order: (usize, usize), // (sensor file, rollover part) for the file list
+    workbook: Workbook,
+    sheets: Vec<((ChannelKey, usize), Worksheet)>,
+    summary: Vec<ChannelSummary>,
+    diagnostics: BTreeMap<SocketAddr, SensorStats>,
+    packets: usize,                        // packets with points in this workbook
+    last_packet: Option<(SocketAddr, u64)>, // packet of the last point; points arrive in capture order
+}
+
+/// The worksheet a channel is currently written to
+struct Sheet {
+    name: String, // name of the first part
+    columns: Vec<Column>,
+    worksheet: Worksheet,
+    book: usize,
+    part: usize,
+    rows: usize, // data rows in the current part
+    points: usize,
+    parts: Vec<(String, String)>,
+    first_book: usize,
+    values: [ValueStats; 5],
+}
+
+impl ExcelWriter {
+    /// Start an export to `output_path`; `sensors` are the sensors whose points will be written
+    pub fn new(
+        output_path: &str,
+        options: &ExcelOptions,
+        source: &SourceInfo,
+        sensors: &BTreeMap<SocketAddr, SensorStats>,
+    ) -> Self {
+        let mut writer = ExcelWriter {
+            options: *options,
+            source: source.clone(),
+            output_path: output_path.to_string(),
+            labels: sensor_labels(sensors.keys().copied()),
+            sensors: sensors.clone(),
+            books: Vec::new(),
+            sheets: HashMap::new(),
+            formats: Formats {
+                header: Format::new()
+                    .set_bold()
+                    .set_background_color(rust_xlsxwriter::Color::RGB(0x4472C4))
+                    .set_font_color(rust_xlsxwriter::Color::White),
+                label: Format::new().set_bold(),
+                number: Format::new().set_num_format("0.0000"),
+                angle: Format::new().set_num_format("0.000"),
+                time: Format::new().set_num_format("0.000000"),
+            },
+        };
+
+        // The combined workbook is written even if no point gets selected
+        if !options.per_sensor_files {
+            writer.open_book(output_path.to_string(), (0, 1), sensors.clone());
+        }
+
+        writer
+    }
+
+    /// Sheet name prefix / file name label of a sensor
+    fn label(&self, sensor: SocketAddr) -> String {
+        self.labels
+            .get(&sensor)
+            .cloned()
+            .unwrap_or_else(|| sensor.ip().to_string().replace(':', "-"))
+    }
+
+    /// Create the first sheet of a channel; it gets the debug columns when the scan saw
+    /// its sensor send Debug layout packets
+    fn first_sheet(&mut self, key: ChannelKey) -> Result<Sheet> {
+        let name = if self.labels.len() > 1 && !self.options.per_sensor_files {
+            format!("{}_Channel_{}", self.label(key.sensor), key.channel)
+        } else {
+            format!("Channel_{}", key.channel)
+        };
+        let debug = self.sensors.get(&key.sensor).is_some_and(|stats| stats.debug);
+        let columns = columns::columns(&self.options.columns, debug);
+
+        let (book, worksheet) = self.new_worksheet(key.sensor, 1, &name, &columns)?;
+        let parts = vec![(self.books[book].path.clone(), name.clone())];
+
+        Ok(Sheet {
+            name,
+            columns,
+            worksheet,
+            book,
+            part: 1,
+            rows: 0,
+            points: 0,
+            parts,
+            first_book: book,
+            values: Default::default(),
+        })
+    }
+
+    /// Close the current sheet of a full channel and continue on the next part
+    fn next_part(&mut self, key: ChannelKey) -> Result<()> {
+        let Some(sheet) = self.sheets.get(&key) else {
+            return Ok(());
+        };
+        let part = sheet.part + 1;
+        let name = continuation_sheet_name(&sheet.name, part);
+        let columns = sheet.columns.clone();
+
+        let (book, worksheet) = self.new_worksheet(key.sensor, part, &name, &columns)?;
+
+        if let Some(sheet) = self.sheets.get_mut(&key) {
+            let finished = std::mem::replace(&mut sheet.worksheet, worksheet);
+            self.books[sheet.book].sheets.push(((key, sheet.part), finished));
+            sheet.parts.push((self.books[book].path.clone(), name));
+            sheet.book = book;
+            sheet.part = part;
+            sheet.rows = 0;
+        }
+
+        Ok(())
+    }
+
+    /// Constant-memory worksheet with headers for `part` of a sensor's channel
+    fn new_worksheet(
+        &mut self,
+        sensor: SocketAddr,
+        part: usize,
+        name: &str,
+        columns: &[Column],
+    ) -> Result<(usize, Worksheet)> {
+        let book = self.book(sensor, part);
+        let mut worksheet = self.books[book].workbook.new_worksheet_with_constant_memory();
+        worksheet.set_name(name)?;
+
+        // Write headers and set column widths
+        for (col, &column) in columns.iter().enumerate() {
+            worksheet.write_with_format(0, col as u16, column.header(), &self.formats.header)?;
+            worksheet.set_column_width(col as u16, column_width(column))?;
         }
 
         // Freeze first row (headers)
         worksheet.set_freeze_panes(1, 0)?;
+
+        Ok((book, worksheet))
     }
 
-    // Save workbook
-    workbook
-        .save(output_path)
-        .with_context(|| format!("Failed to save Excel file: {}", output_path))?;
+    /// Workbook that holds `part` of a sensor's channels, opened on first use
+    fn book(&mut self, sensor: SocketAddr, part: usize) -> usize {
+        let part = match self.options.split {
+            SplitMode::Sheets => 1,
+            SplitMode::Workbooks => part,
+        };
+
+        let (base, index) = if self.options.per_sensor_files {
+            let index = self.labels.keys().position(|s| *s == sensor).unwrap_or(self.labels.len());
+            (sensor_output_path(&self.output_path, &self.label(sensor)), index)
+        } else {
+            (self.output_path.clone(), 0)
+        };
+        let path = part_output_path(&base, part);
+
+        if let Some(book) = self.books.iter().position(|book| book.path == path) {
+            return book;
+        }
+
+        // Diagnostics go into the first workbook of each output only
+        let diagnostics = if part > 1 {
+            BTreeMap::new()
+        } else {
+            self.sensors
+                .iter()
+                .filter(|(addr, _)| !self.options.per_sensor_files || **addr == sensor)
+                .map(|(addr, stats)| (*addr, stats.clone()))
+                .collect()
+        };
+        self.open_book(path, (index, part), diagnostics)
+    }
+
+    fn open_book(
+        &mut self,
+        path: String,
+        order: (usize, usize),
+        diagnostics: BTreeMap<SocketAddr, SensorStats>,
+    ) -> usize {
+        self.books.push(Book {
+            path,
+            order,
+            workbook: Workbook::new(),
+            sheets: Vec::new(),
+            summary: Vec::new(),
+            diagnostics,
+            packets: 0,
+            last_packet: None,
+        });
+        self.books.len() - 1
+    }
+}
+
+impl PointWriter for ExcelWriter {
+    /// Append one point to its channel sheet
+    fn write_point(&mut self, key: ChannelKey, point: &Point) -> Result<()> {
+        let rows_per_sheet = self.options.max_rows.saturating_sub(1).max(1);
+
+        match self.sheets.get(&key) {
+            None => {
+                let sheet = self.first_sheet(key)?;
+                self.sheets.insert(key, sheet);
+            }
+            Some(sheet) if sheet.rows == rows_per_sheet => self.next_part(key)?,
+            Some(_) => {}
+        }
+
+        let Some(sheet) = self.sheets.get_mut(&key) else {
+            return Ok(());
+        };
+        sheet.rows += 1;
+        sheet.points += 1;
+
+        let book = &mut self.books[sheet.book];
+        let packet = Some((key.sensor, point.packet_index));
+        if book.last_packet != packet {
+            book.last_packet = packet;
+            book.packets += 1;
+        }
+
+        let values = [point.x, point.y, point.z, point.range(), point.reflectivity as f64];
+        for (stats, value) in sheet.values.iter_mut().zip(values) {
+            stats.push(value);
+        }
+
+        write_row(&mut sheet.worksheet, sheet.rows as u32, &sheet.columns, point, &self.formats)
+    }
+
+    /// Add all sheets to their workbooks and save them
+    fn finish(self: Box<Self>) -> Result<ExportSummary> {
+        let mut this = *self;
+        let mut summary = ExportSummary::default();
+
+        let mut keys: Vec<_> = this.sheets.keys().copied().collect();
+        keys.sort();
+
+        for key in keys {
+            let Some(sheet) = this.sheets.remove(&key) else {
+                continue;
+            };
+            this.books[sheet.book].sheets.push(((key, sheet.part), sheet.worksheet));
+            this.books[sheet.first_book].summary.push(ChannelSummary {
+                key,
+                sheet: sheet.name.clone(),
+                points: sheet.points,
+                parts: sheet.parts.len(),
+                values: sheet.values,
+            });
+
+            if sheet.parts.len() > 1 {
+                summary.split_sheets.push(SplitSheet {
+                    sheet: sheet.name,
+                    points: sheet.points,
+                    parts: sheet.parts,
+                });
+            }
+        }
+
+        this.books.sort_by_key(|book| book.order);
+
+        for mut book in this.books {
+            // Summary first, in the first workbook of each output
+            if book.order.1 == 1 {
+                write_summary_sheet(&mut book, &this.source, &this.formats)?;
+            }
+
+            book.sheets.sort_by_key(|(order, _)| *order);
+            for (_, worksheet) in book.sheets {
+                book.workbook.push_worksheet(worksheet);
+            }
+
+            if !book.diagnostics.is_empty() {
+                write_diagnostics_sheet(&mut book.workbook, &book.diagnostics, &this.formats.header)?;
+            }
+
+            // Save workbook
+            book.workbook
+                .save(&book.path)
+                .with_context(|| format!("Failed to save Excel file: {}", book.path))?;
+            summary.files.push(book.path);
+        }
+
+        Ok(summary)
+    }
+}
+
+/// Overview sheet: source, parse mode, packets that contributed points, capture time span, time window, region of interest, sensor transforms and per-channel statistics
+fn write_summary_sheet(book: &mut Book, source: &SourceInfo, formats: &Formats) -> Result<()> {
+    let worksheet = book.workbook.add_worksheet();
+    worksheet.set_name("Summary")?;
+    worksheet.set_column_width(0, 26)?;
+    worksheet.set_column_width(1, 20)?;
+
+    let first = book.diagnostics.values().filter_map(|stats| stats.time_span).map(|(first, _)| first).min();
+    let last = book.diagnostics.values().filter_map(|stats| stats.time_span).map(|(_, last)| last).max();
+
+    let date_format = Format::new().set_num_format("yyyy-mm-dd hh:mm:ss.000");
+    let seconds_format = Format::new().set_num_format("0.000000");
+
+    worksheet.write_with_format(0, 0, "Source File", &formats.label)?;
+    worksheet.write_string(0, 1, &source.file)?;
+    worksheet.write_with_format(1, 0, "Parse Mode", &formats.label)?;
+    worksheet.write_string(1, 1, &source.mode)?;
+    worksheet.write_with_format(2, 0, "Packets With Points", &formats.label)?;
+    worksheet.write_number(2, 1, book.packets as f64)?;
+    worksheet.write_with_format(3, 0, "Capture Start (UTC)", &formats.label)?;
+    worksheet.write_with_format(4, 0, "Capture End (UTC)", &formats.label)?;
+    worksheet.write_with_format(5, 0, "Duration (s)", &formats.label)?;
+    if let (Some(first), Some(last)) = (first, last) {
+        worksheet.write_with_format(3, 1, excel_datetime(first), &date_format)?;
+        worksheet.write_with_format(4, 1, excel_datetime(last), &date_format)?;
+        worksheet.write_with_format(5, 1, (last - first) as f64 / 1e9, &seconds_format)?;
+    }
+    worksheet.write_with_format(6, 0, "Time Window", &formats.label)?;
+    worksheet.write_string(6, 1, source.window.as_deref().unwrap_or("Whole capture"))?;
+    worksheet.write_with_format(7, 0, "Region of Interest", &formats.label)?;
+    worksheet.write_string(7, 1, source.region.as_deref().unwrap_or("All points"))?;
+    worksheet.write_with_format(8, 0, "Sensor Transform", &formats.label)?;
+    worksheet.write_string(8, 1, source.transform.as_deref().unwrap_or("None (sensor frame)"))?;
+
+    // Per-channel table
+    let table_row = 10;
+    let mut headers: Vec<String> = ["Sheet", "Sensor", "Channel", "Points", "Parts"].map(String::from).to_vec();
+    for value in SUMMARY_VALUES {
+        let unit = if value == "Reflectivity" { "" } else { " (m)" };
+        headers.extend(["Min", "Max", "Mean"].map(|stat| format!("{} {}{}", value, stat, unit)));
+    }
+    for (col, header) in headers.iter().enumerate() {
+        worksheet.write_with_format(table_row, col as u16, header, &formats.header)?;
+        if col >= 2 {
+            worksheet.set_column_width(col as u16, 14)?;
+        }
+    }
+
+    book.summary.sort_by_key(|channel| channel.key);
+
+    for (i, channel) in book.summary.iter().enumerate() {
+        let row = table_row + 1 + i as u32;
+
+        let link = Url::new(format!("internal:'{}'!A1", channel.sheet)).set_text(&channel.sheet);
+        worksheet.write_url(row, 0, link)?;
+        worksheet.write_string(row, 1, channel.key.sensor.to_string())?;
+        worksheet.write_number(row, 2, channel.key.channel as f64)?;
+        worksheet.write_number(row, 3, channel.points as f64)?;
+        worksheet.write_number(row, 4, channel.parts as f64)?;
+
+        for (v, stats) in channel.values.iter().enumerate() {
+            let col = 5 + 3 * v as u16;
+            worksheet.write_with_format(row, col, stats.min, &formats.number)?;
+            worksheet.write_with_format(row, col + 1, stats.max, &formats.number)?;
+            worksheet.write_with_format(row, col + 2, stats.mean(), &formats.number)?;
+        }
+    }
+
+    worksheet.set_freeze_panes(table_row + 1, 1)?;
+
+    Ok(())
+}
+
+/// Unix nanoseconds as an Excel date serial number (days since 1899-12-30)
+fn excel_datetime(unix_ns: u64) -> f64 {
+    const UNIX_EPOCH_SERIAL: f64 = 25569.0;
+    let secs = unix_ns / 1_000_000_000;
+    let nanos = unix_ns % 1_000_000_000;
+    UNIX_EPOCH_SERIAL + (secs as f64 + nanos as f64 / 1e9) / 86400.0
+}
+
+/// Write the cells of one point
+fn write_row(
+    worksheet: &mut Worksheet,
+    row: u32,
+    columns: &[Column],
+    point: &Point,
+    formats: &Formats,
+) -> Result<()> {
+    for (col, &column) in columns.iter().enumerate() {
+        let col = col as u16;
+        let Some(value) = column.value(point) else {
+            continue;
+        };
+
+        match column {
+            Column::X | Column::Y | Column::Z | Column::Range | Column::RangeError(_) => {
+                worksheet.write_with_format(row, col, value, &formats.number)?;
+            }
+            Column::Azimuth | Column::Elevation => {
+                worksheet.write_with_format(row, col, value, &formats.angle)?;
+            }
+            Column::CaptureTime => {
+                worksheet.write_with_format(row, col, value, &formats.time)?;
+            }
+            _ => {
+                worksheet.write_number(row, col, value)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Sheet name of part `part` (1-based) of a channel: "Channel_5", "Channel_5_part2", ...
+fn continuation_sheet_name(name: &str, part: usize) -> String {
+    if part == 1 {
+        return name.to_string();
+    }
+
+    let long = format!("{}_part{}", name, part);
+    if long.len() <= MAX_SHEET_NAME_LEN {
+        return long;
+    }
+
+    // Shorter suffix, and cut the base name if even that does not fit
+    let suffix = format!("_p{}", part);
+    let base_len = name.len().min(MAX_SHEET_NAME_LEN - suffix.len());
+    format!("{}{}", &name[..base_len], suffix)
+}
+
+/// Path of rollover workbook `part` (1-based): "out.xlsx", "out_part2.xlsx", ...
+pub fn part_output_path(output_path: &str, part: usize) -> String {
+    if part == 1 {
+        output_path.to_string()
+    } else {
+        sensor_output_path(output_path, &format!("part{}", part))
+    }
+}
+
+/// Excel column width for each column
+fn column_width(column: Column) -> f64 {
+    match column {
+        Column::X | Column::Y | Column::Z => 12.0,
+        Column::Reflectivity => 14.0,
+        Column::Flags => 10.0,
+        Column::FlagBit(_) => 14.0,
+        Column::Distance | Column::Intensity | Column::PowerLevel => 12.0,
+        Column::Range | Column::Azimuth | Column::Elevation => 14.0,
+        Column::RangeError(_) => 20.0,
+        Column::SensorTime => 18.0,
+        Column::CaptureTime => 20.0,
+        Column::Channel | Column::PointIndex | Column::Frame => 10.0,
+        Column::SequenceId | Column::PacketIndex => 12.0,
+    }
+}
+
+/// Per-sensor packet loss / sequence integrity sheet
+fn write_diagnostics_sheet(
+    workbook: &mut Workbook,
+    sensors: &BTreeMap<SocketAddr, SensorStats>,
+    header_format: &Format,
+) -> Result<()> {
+    let worksheet = workbook.add_worksheet();
+    worksheet.set_name("Diagnostics")?;
+
+    let headers = [
+        ("Sensor", 24),
+        ("Packets", 10),
+        ("Points", 12),
+        ("First Seq", 12),
+        ("Last Seq", 12),
+        ("Expected Packets", 16),
+        ("Received Packets", 16),
+        ("Lost Packets", 12),
+        ("Loss (%)", 10),
+        ("Est. Lost Points", 16),
+        ("Gaps", 8),
+        ("Duplicates", 10),
+        ("Out of Order", 12),
+        ("Resets", 8),
+    ];
+    for (col, (header, width)) in headers.iter().enumerate() {
+        worksheet.write_with_format(0, col as u16, *header, header_format)?;
+        worksheet.set_column_width(col as u16, *width)?;
+    }
+
+    let percent_format = Format::new().set_num_format("0.00");
+
+    for (i, (addr, stats)) in sensors.iter().enumerate() {
+        let row = (i + 1) as u32;
+        let seq = &stats.sequence;
+
+        worksheet.write_string(row, 0, addr.to_string())?;
+        worksheet.write_number(row, 1, stats.packets as f64)?;
+        worksheet.write_number(row, 2, stats.points as f64)?;
+        if let Some(first) = seq.first_seq {
+            worksheet.write_number(row, 3, first as f64)?;
+        }
+        if let Some(last) = seq.last_seq {
+            worksheet.write_number(row, 4, last as f64)?;
+        }
+        worksheet.write_number(row, 5, seq.expected as f64)?;
+        worksheet.write_number(row, 6, seq.received as f64)?;
+        worksheet.write_number(row, 7, seq.lost as f64)?;
+        worksheet.write_with_format(row, 8, seq.loss_ratio() * 100.0, &percent_format)?;
+        worksheet.write_number(row, 9, stats.lost_points_estimate() as f64)?;
+        worksheet.write_number(row, 10, seq.gaps as f64)?;
+        worksheet.write_number(row, 11, seq.duplicates as f64)?;
+        worksheet.write_number(row, 12, seq.out_of_order as f64)?;
+        worksheet.write_number(row, 13, seq.resets as f64)?;
+    }
+
+    worksheet.set_freeze_panes(1, 0)?;
 
     Ok(())
 }
@@ -118,13 +621,30 @@ pub fn export_to_excel(channel_points: &HashMap<u8, Vec<Point>>, output_path: &s
 mod tests {
     use super::*;
 
+    /// Stream every point of `channel_points` through an ExcelWriter
+    fn export(
+        channel_points: &HashMap<ChannelKey, Vec<Point>>,
+        options: &ExcelOptions,
+        sensors: &BTreeMap<SocketAddr, SensorStats>,
+        output_path: &str,
+    ) -> Result<ExportSummary> {
+        let mut writer = Box::new(ExcelWriter::new(output_path, options, &SourceInfo::default(), sensors));
+        for (key, points) in channel_points {
+            for point in points {
+                writer.write_point(*key, point)?;
+            }
+        }
+        writer.finish()
+    }
+
     #[test]
     fn test_export_to_excel() {
-        let mut channel_points: HashMap<u8, Vec<Point>> = HashMap::new();
+        let sensor: SocketAddr = "192.168.1.201:8808".parse().unwrap();
+        let mut channel_points: HashMap<ChannelKey, Vec<Point>> = HashMap::new();
 
         // Add some test points
         channel_points.insert(
-            0,
+            ChannelKey { sensor, channel: 0 },
             vec![
                 Point {
                     x: 12.8,
@@ -132,9 +652,7 @@ mod tests {
                     z: -88.94,
                     reflectivity: 128,
                     flags: 0,
-                    distance: None,
-                    intensity: None,
-                    power_level: None,
+                    ..Default::default()
                 },
                 Point {
                     x: 82.32,
@@ -142,32 +660,134 @@ mod tests {
                     z: 4.05,
                     reflectivity: 255,
                     flags: 1,
-                    distance: None,
-                    intensity: None,
-                    power_level: None,
+                    ..Default::default()
                 },
             ],
         );
 
         channel_points.insert(
-            5,
+            ChannelKey { sensor, channel: 5 },
             vec![Point {
                 x: -1.05,
                 y: 5.22,
                 z: 0.32,
                 reflectivity: 64,
                 flags: 0,
-                distance: None,
-                intensity: None,
-                power_level: None,
+                ..Default::default()
             }],
         );
 
         // Export to test file
-        let result = export_to_excel(&channel_points, "test_output.xlsx");
-        assert!(result.is_ok());
+        let options = ExcelOptions {
+            columns: ColumnOptions { timestamps: true, trace: true, ..Default::default() },
+            ..Default::default()
+        };
+        let mut sensors = BTreeMap::new();
+        sensors.insert(sensor, SensorStats { packets: 2, points: 3, matched: true, ..Default::default() });
+
+        let summary = export(&channel_points, &options, &sensors, "test_output.xlsx").unwrap();
+        assert_eq!(summary.files, vec!["test_output.xlsx".to_string()]);
+        assert!(summary.split_sheets.is_empty());
 
         // Clean up
         let _ = std::fs::remove_file("test_output.xlsx");
     }
+
+    #[test]
+    fn test_split_at_row_limit() {
+        let sensor: SocketAddr = "192.168.1.201:8808".parse().unwrap();
+        let mut channel_points: HashMap<ChannelKey, Vec<Point>> = HashMap::new();
+        channel_points.insert(ChannelKey { sensor, channel: 5 }, vec![Point::default(); 7]);
+        channel_points.insert(ChannelKey { sensor, channel: 6 }, vec![Point::default(); 2]);
+
+        // 3 data rows per sheet
+        let mut options = ExcelOptions { max_rows: 4, ..Default::default() };
+        let sensors = BTreeMap::new();
+
+        let summary = export(&channel_points, &options, &sensors, "test_split.xlsx").unwrap();
+        assert_eq!(summary.files, vec!["test_split.xlsx".to_string()]);
+        assert_eq!(summary.split_sheets.len(), 1);
+        assert_eq!(summary.split_sheets[0].sheet, "Channel_5");
+        assert_eq!(summary.split_sheets[0].points, 7);
+        assert_eq!(
+            summary.split_sheets[0].parts.iter().map(|(_, sheet)| sheet.as_str()).collect::<Vec<_>>(),
+            vec!["Channel_5", "Channel_5_part2", "Channel_5_part3"]
+        );
+        let _ = std::fs::remove_file("test_split.xlsx");
+
+        options.split = SplitMode::Workbooks;
+        let summary = export(&channel_points, &options, &sensors, "test_rollover.xlsx").unwrap();
+        assert_eq!(
+            summary.files,
+            vec!["test_rollover.xlsx", "test_rollover_part2.xlsx", "test_rollover_part3.xlsx"]
+        );
+        assert_eq!(summary.split_sheets[0].parts[2].0, "test_rollover_part3.xlsx");
+        for file in &summary.files {
+            let _ = std::fs::remove_file(file);
+        }
+    }
+
+    #[test]
+    fn test_per_sensor_files() {
+        let a: SocketAddr = "192.168.1.201:8808".parse().unwrap();
+        let b: SocketAddr = "192.168.1.202:8808".parse().unwrap();
+        let options = ExcelOptions { per_sensor_files: true, ..Default::default() };
+        let mut sensors = BTreeMap::new();
+        sensors.insert(a, SensorStats::default());
+        sensors.insert(b, SensorStats::default());
+
+        // Interleaved arrival order does not matter
+        let mut writer = Box::new(ExcelWriter::new("test_sensors.xlsx", &options, &SourceInfo::default(), &sensors));
+        for key in [ChannelKey { sensor: b, channel: 3 }, ChannelKey { sensor: a, channel: 1 }] {
+            writer.write_point(key, &Point::default()).unwrap();
+        }
+        let summary = writer.finish().unwrap();
+
+        assert_eq!(
+            summary.files,
+            vec!["test_sensors_192.168.1.201.xlsx", "test_sensors_192.168.1.202.xlsx"]
+        );
+        for file in &summary.files {
+            let _ = std::fs::remove_file(file);
+        }
+    }
+
+    #[test]
+    fn test_summary_packets() {
+        let sensor: SocketAddr = "192.168.1.201:8808".parse().unwrap();
+        let mut sensors = BTreeMap::new();
+        sensors.insert(sensor, SensorStats { packets: 10, points: 40, ..Default::default() });
+
+        // Packets 1-3 and 6-10 were filtered out; packet 4 spans two channels
+        let mut writer = ExcelWriter::new("test_packets.xlsx", &ExcelOptions::default(), &SourceInfo::default(), &sensors);
+        for (channel, packet_index) in [(1, 4), (2, 4), (1, 4), (2, 5)] {
+            let point = Point { packet_index, ..Default::default() };
+            writer.write_point(ChannelKey { sensor, channel }, &point).unwrap();
+        }
+        assert_eq!(writer.books[0].packets, 2);
+    }
+
+    #[test]
+    fn test_summary_values() {
+        let mut stats = ValueStats::default();
+        for value in [2.0, -1.0, 5.0] {
+            stats.push(value);
+        }
+        assert_eq!(stats.min, -1.0);
+        assert_eq!(stats.max, 5.0);
+        assert_eq!(stats.mean(), 2.0);
+        assert_eq!(ValueStats::default().mean(), 0.0);
+
+        // 2023-11-14 22:13:20.5 UTC
+        let serial = excel_datetime(1_700_000_000_500_000_000);
+        assert!((serial - (45244.0 + 80000.5 / 86400.0)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_continuation_sheet_name() {
+        assert_eq!(continuation_sheet_name("Channel_5", 1), "Channel_5");
+        assert_eq!(continuation_sheet_name("Channel_5", 2), "Channel_5_part2");
+        assert_eq!(continuation_sheet_name("255.255.255.255_Channel_255", 2), "255.255.255.255_Channel_255_p2");
+        assert!(continuation_sheet_name("Sensor12_Channel_255_abcdefghij", 10).len() <= MAX_SHEET_NAME_LEN);
+    }
 }
@@ -0,0 +1,362 @@
+//! Output formats and the pieces shared by all exporters
+
+use crate::cepton::{ChannelKey, Point};
+use crate::pcap_reader::SensorStats;
+use anyhow::Result;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt;
+use std::net::SocketAddr;
+use std::path::Path;
+
+/// Longest sheet name Excel accepts; sensor labels are kept short enough for it
+pub const MAX_SHEET_NAME_LEN: usize = 31;
+
+/// Output file format
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum OutputFormat {
+    #[default]
+    Xlsx,
+    Csv,
+    Tsv,
+    Pcd,
+    Ply,
+    Las,
+    Parquet,
+    Npy,
+    Npz,
+}
+
+impl OutputFormat {
+    /// File extension, without the dot
+    pub fn extension(self) -> &'static str {
+        match self {
+            OutputFormat::Xlsx => "xlsx",
+            OutputFormat::Csv => "csv",
+            OutputFormat::Tsv => "tsv",
+            OutputFormat::Pcd => "pcd",
+            OutputFormat::Ply => "ply",
+            OutputFormat::Las => "las",
+            OutputFormat::Parquet => "parquet",
+            OutputFormat::Npy => "npy",
+            OutputFormat::Npz => "npz",
+        }
+    }
+
+    /// Format matching the extension of `path`, if any
+    pub fn from_path(path: &str) -> Option<OutputFormat> {
+        let ext = Path::new(path).extension()?.to_str()?.to_lowercase();
+        match ext.as_str() {
+            "xlsx" => Some(OutputFormat::Xlsx),
+            "csv" => Some(OutputFormat::Csv),
+            "tsv" => Some(OutputFormat::Tsv),
+            "pcd" => Some(OutputFormat::Pcd),
+            "ply" => Some(OutputFormat::Ply),
+            "las" => Some(OutputFormat::Las),
+            "parquet" => Some(OutputFormat::Parquet),
+            "npy" => Some(OutputFormat::Npy),
+            "npz" => Some(OutputFormat::Npz),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for OutputFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            OutputFormat::Xlsx => write!(f, "Excel"),
+            OutputFormat::Csv => write!(f, "CSV"),
+            OutputFormat::Tsv => write!(f, "TSV"),
+            OutputFormat::Pcd => write!(f, "PCD"),
+            OutputFormat::Ply => write!(f, "PLY"),
+            OutputFormat::Las => write!(f, "LAS"),
+            OutputFormat::Parquet => write!(f, "Parquet"),
+            OutputFormat::Npy => write!(f, "NumPy"),
+            OutputFormat::Npz => write!(f, "NumPy bundle"),
+        }
+    }
+}
+
+/// How points are spread over output files (all formats but Excel)
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Layout {
+    #[default]
+    PerChannel, // one file per channel: out_Channel_5.csv, ...
+    Merged,     // all channels in one file, with a channel column
+}
+
+/// Where the exported points came from, for the Summary sheet and file metadata
+#[derive(Debug, Clone, Default)]
+pub struct SourceInfo {
+    pub file: String,
+    pub mode: String,           // parse mode description
+    pub window: Option<String>, // time window description, when one was set
+    pub region: Option<String>, // region of interest description, when one was set
+    pub transform: Option<String>, // sensor transforms, when any was set
+}
+
+/// Destination of the extracted points, fed one point at a time
+pub trait PointWriter {
+    /// Append one point of a sensor's channel
+    fn write_point(&mut self, key: ChannelKey, point: &Point) -> Result<()>;
+
+    /// No point of a frame below `frame` will follow; writers that keep frames apart may close them
+    fn frames_complete(&mut self, _frame: u64) -> Result<()> {
+        Ok(())
+    }
+
+    /// Flush and close every output file
+    fn finish(self: Box<Self>) -> Result<ExportSummary>;
+}
+
+/// A channel that did not fit into a single Excel worksheet
+#[derive(Debug, Clone, PartialEq)]
+pub struct SplitSheet {
+    pub sheet: String,
+    pub points: usize,
+    pub parts: Vec<(String, String)>, // (file, sheet) of every part
+}
+
+/// Files written by an export and how oversized channels were split
+#[derive(Debug, Default)]
+pub struct ExportSummary {
+    pub files: Vec<String>,
+    pub split_sheets: Vec<SplitSheet>,
+}
+
+/// Output file of every (sensor, channel) for the file based formats
+#[derive(Debug, Clone)]
+pub struct OutputFiles {
+    output_path: String,
+    layout: Layout,
+    per_sensor_files: bool,
+    labels: BTreeMap<SocketAddr, String>,
+    debug_sensors: BTreeSet<SocketAddr>, // sensors with Debug layout packets
+}
+
+impl OutputFiles {
+    pub fn new(
+        output_path: &str,
+        layout: Layout,
+        per_sensor_files: bool,
+        sensors: impl Iterator<Item = SocketAddr>,
+    ) -> Self {
+        OutputFiles {
+            output_path: output_path.to_string(),
+            layout,
+            per_sensor_files,
+            labels: sensor_labels(sensors),
+            debug_sensors: BTreeSet::new(),
+        }
+    }
+
+    /// Output files of the sensors in `sensors`; the files of those the scan saw sending
+    /// Debug layout packets get the Debug fields
+    pub fn for_sensors(
+        output_path: &str,
+        layout: Layout,
+        per_sensor_files: bool,
+        sensors: &BTreeMap<SocketAddr, SensorStats>,
+    ) -> Self {
+        OutputFiles {
+            debug_sensors: sensors.iter().filter(|(_, stats)| stats.debug).map(|(addr, _)| *addr).collect(),
+            ..OutputFiles::new(output_path, layout, per_sensor_files, sensors.keys().copied())
+        }
+    }
+
+    /// True when the file of `key` receives points with the Debug mode fields
+    pub fn has_debug(&self, key: ChannelKey) -> bool {
+        if self.mixes_sensors() {
+            !self.debug_sensors.is_empty()
+        } else {
+            self.debug_sensors.contains(&key.sensor)
+        }
+    }
+
+    /// True when one file holds several sensors
+    pub fn mixes_sensors(&self) -> bool {
+        self.layout == Layout::Merged && !self.per_sensor_files && self.labels.len() > 1
+    }
+
+    /// File that receives the points of `key`
+    pub fn path(&self, key: ChannelKey) -> String {
+        // The sensor label is only needed to tell several sensors apart
+        let label = (self.per_sensor_files || self.labels.len() > 1).then(|| self.label(key.sensor));
+
+        let suffix = match (self.layout, label) {
+            (Layout::PerChannel, Some(label)) => format!("{}_Channel_{}", label, key.channel),
+            (Layout::PerChannel, None) => format!("Channel_{}", key.channel),
+            (Layout::Merged, Some(label)) if self.per_sensor_files => label,
+            (Layout::Merged, _) => return self.output_path.clone(),
+        };
+        sensor_output_path(&self.output_path, &suffix)
+    }
+
+    /// Label of a sensor; sensors unknown at construction get their IP address
+    pub fn label(&self, sensor: SocketAddr) -> String {
+        self.labels
+            .get(&sensor)
+            .cloned()
+            .unwrap_or_else(|| sensor.ip().to_string().replace(':', "-"))
+    }
+}
+
+/// Output files opened on first use, one per path of `OutputFiles`
+pub struct OpenFiles<T> {
+    files: OutputFiles,
+    outputs: Vec<OpenFile<T>>,
+    by_path: HashMap<String, usize>,
+    by_key: HashMap<ChannelKey, usize>, // avoids building the path for every point
+}
+
+/// One open output file and the writer state of its format
+pub struct OpenFile<T> {
+    pub path: String,
+    first_key: ChannelKey, // for a stable file order
+    pub output: T,
+}
+
+impl<T> OpenFiles<T> {
+    pub fn new(files: OutputFiles) -> Self {
+        OpenFiles {
+            files,
+            outputs: Vec::new(),
+            by_path: HashMap::new(),
+            by_key: HashMap::new(),
+        }
+    }
+
+    pub fn files(&self) -> &OutputFiles {
+        &self.files
+    }
+
+    /// File that receives `key`, created with `open(path)` on first use
+    pub fn get_or_open<F>(&mut self, key: ChannelKey, open: F) -> Result<&mut OpenFile<T>>
+    where
+        F: FnOnce(&str) -> Result<T>,
+    {
+        let index = match self.by_key.get(&key) {
+            Some(&index) => index,
+            None => {
+                let path = self.files.path(key);
+                let index = match self.by_path.get(&path) {
+                    Some(&index) => index,
+                    None => {
+                        let output = open(&path)?;
+                        self.outputs.push(OpenFile { path: path.clone(), first_key: key, output });
+                        self.by_path.insert(path, self.outputs.len() - 1);
+                        self.outputs.len() - 1
+                    }
+                };
+                self.by_key.insert(key, index);
+                index
+            }
+        };
+
+        Ok(&mut self.outputs[index])
+    }
+
+    /// All files, ordered by the first sensor and channel written to them
+    pub fn into_sorted(self) -> Vec<OpenFile<T>> {
+        let mut outputs = self.outputs;
+        outputs.sort_by_key(|output| output.first_key);
+        outputs
+    }
+}
+
+/// Short, sheet- and file-name safe label for every sensor.
+/// Uses the IP address, adds the port when several sensors share an IP,
+/// and falls back to "Sensor<n>" when the label would not fit a sheet name.
+pub fn sensor_labels(sensors: impl Iterator<Item = SocketAddr>) -> BTreeMap<SocketAddr, String> {
+    let sensors: BTreeSet<SocketAddr> = sensors.collect();
+    let ips: BTreeSet<_> = sensors.iter().map(|addr| addr.ip()).collect();
+    let with_port = ips.len() < sensors.len();
+
+    let mut labels: BTreeMap<SocketAddr, String> = sensors
+        .iter()
+        .map(|addr| {
+            let ip = addr.ip().to_string().replace(':', "-");
+            let label = if with_port { format!("{}_{}", ip, addr.port()) } else { ip };
+            (*addr, label)
+        })
+        .collect();
+
+    let longest = labels.values().map(|label| label.len()).max().unwrap_or(0);
+    if longest + "_Channel_255".len() > MAX_SHEET_NAME_LEN {
+        for (i, label) in labels.values_mut().enumerate() {
+            *label = format!("Sensor{}", i + 1);
+        }
+    }
+
+    labels
+}
+
+/// "out.xlsx" + "192.168.1.201" -> "out_192.168.1.201.xlsx"
+pub fn sensor_output_path(output_path: &str, label: &str) -> String {
+    let path = Path::new(output_path);
+    let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or("output");
+    let ext = path.extension().and_then(|s| s.to_str()).unwrap_or("xlsx");
+    path.with_file_name(format!("{}_{}.{}", stem, label, ext))
+        .to_string_lossy()
+        .into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sensor_labels() {
+        let a: SocketAddr = "192.168.1.201:8808".parse().unwrap();
+        let b: SocketAddr = "192.168.1.202:8808".parse().unwrap();
+        let c: SocketAddr = "192.168.1.202:8809".parse().unwrap();
+        let v6: SocketAddr = "[fe80::1234:5678:9abc:def0]:8808".parse().unwrap();
+
+        let labels = sensor_labels([a, b].into_iter());
+        assert_eq!(labels[&a], "192.168.1.201");
+        assert_eq!(labels[&b], "192.168.1.202");
+
+        // Shared IP: port is added
+        let labels = sensor_labels([a, b, c].into_iter());
+        assert_eq!(labels[&b], "192.168.1.202_8808");
+        assert_eq!(labels[&c], "192.168.1.202_8809");
+
+        // Too long for a sheet name: numbered labels
+        let labels = sensor_labels([a, v6].into_iter());
+        assert_eq!(labels[&a], "Sensor1");
+        assert_eq!(labels[&v6], "Sensor2");
+
+        assert_eq!(sensor_output_path("out/run.xlsx", "Sensor1"), "out/run_Sensor1.xlsx");
+    }
+
+    #[test]
+    fn test_output_files() {
+        let a: SocketAddr = "192.168.1.201:8808".parse().unwrap();
+        let b: SocketAddr = "192.168.1.202:8808".parse().unwrap();
+        let key = ChannelKey { sensor: a, channel: 5 };
+
+        let files = OutputFiles::new("out/run.csv", Layout::PerChannel, false, [a].into_iter());
+        assert_eq!(files.path(key), "out/run_Channel_5.csv");
+
+        let files = OutputFiles::new("out/run.csv", Layout::PerChannel, false, [a, b].into_iter());
+        assert_eq!(files.path(key), "out/run_192.168.1.201_Channel_5.csv");
+
+        let files = OutputFiles::new("out/run.csv", Layout::Merged, false, [a, b].into_iter());
+        assert_eq!(files.path(key), "out/run.csv");
+        assert!(files.mixes_sensors());
+
+        let files = OutputFiles::new("out/run.csv", Layout::Merged, true, [a, b].into_iter());
+        assert_eq!(files.path(key), "out/run_192.168.1.201.csv");
+        assert!(!files.mixes_sensors());
+
+        // Debug fields go to the files of the sensors that sent Debug layout packets
+        let sensors = BTreeMap::from([(a, SensorStats::default()), (b, SensorStats { debug: true, ..Default::default() })]);
+        let files = OutputFiles::for_sensors("out/run.csv", Layout::PerChannel, false, &sensors);
+        assert!(!files.has_debug(key));
+        assert!(files.has_debug(ChannelKey { sensor: b, channel: 5 }));
+        let files = OutputFiles::for_sensors("out/run.csv", Layout::Merged, false, &sensors);
+        assert!(files.has_debug(key));
+
+        assert_eq!(OutputFormat::from_path("a/b.TSV"), Some(OutputFormat::Tsv));
+        assert_eq!(OutputFormat::from_path("a/b.txt"), None);
+    }
+}
@@ -0,0 +1,104 @@
+//! Point flag bits - names of the bits in `RawPoint.flags` / `Point.flags`,
+//! as defined by the `CEPTON_POINT_*` values of the Cepton SDK 2 header `cepton_sdk2.h`.
+//!
+//! There is no ambient bit: the header assigns all eight bits of the byte to the flags
+//! below, and STDV points carry no ambient / no-signal marker. Points without an echo
+//! are reported by `no_return`.
+
+use std::fmt;
+
+/// One bit of the point flags
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PointFlag {
+    pub name: &'static str,   // command-line name
+    pub header: &'static str, // column header
+    pub mask: u8,
+}
+
+/// Every flag bit, lowest first
+pub const POINT_FLAGS: [PointFlag; 8] = [
+    PointFlag { name: "saturated", header: "Saturated", mask: 1 << 0 },
+    PointFlag { name: "blooming", header: "Blooming", mask: 1 << 1 },
+    PointFlag { name: "frame_parity", header: "Frame Parity", mask: 1 << 2 },
+    PointFlag { name: "frame_boundary", header: "Frame Boundary", mask: 1 << 3 },
+    PointFlag { name: "second_return", header: "Second Return", mask: 1 << 4 },
+    PointFlag { name: "no_return", header: "No Return", mask: 1 << 5 },
+    PointFlag { name: "noise", header: "Noise", mask: 1 << 6 },
+    PointFlag { name: "blocked", header: "Blocked", mask: 1 << 7 },
+];
+
+/// Flag with this command-line name; '-' and '_' are interchangeable
+pub fn find_flag(name: &str) -> Option<&'static PointFlag> {
+    let name = name.trim().to_lowercase().replace('-', "_");
+    POINT_FLAGS.iter().find(|flag| flag.name == name)
+}
+
+/// A set of flag bits, shown by name
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct FlagMask(pub u8);
+
+impl FlagMask {
+    /// True when `flags` has any of the bits
+    pub fn matches(self, flags: u8) -> bool {
+        flags & self.0 != 0
+    }
+
+    pub fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+}
+
+impl fmt::Display for FlagMask {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let names: Vec<&str> = POINT_FLAGS.iter().filter(|flag| self.matches(flag.mask)).map(|flag| flag.name).collect();
+        if names.is_empty() {
+            write!(f, "none")
+        } else {
+            write!(f, "{}", names.join(", "))
+        }
+    }
+}
+
+/// Points per flag bit
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct FlagCounts(pub [usize; 8]);
+
+impl FlagCounts {
+    pub fn push(&mut self, flags: u8) {
+        for (count, flag) in self.0.iter_mut().zip(&POINT_FLAGS) {
+            if flags & flag.mask != 0 {
+                *count += 1;
+            }
+        }
+    }
+
+    /// (flag, points) of the bits that were seen at least once
+    pub fn seen(&self) -> impl Iterator<Item = (&'static PointFlag, usize)> + '_ {
+        POINT_FLAGS.iter().zip(self.0).filter(|&(_, count)| count > 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_point_flags() {
+        assert_eq!(find_flag("No-Return").map(|flag| flag.mask), Some(0x20));
+        assert_eq!(find_flag("saturated").map(|flag| flag.mask), Some(0x01));
+        assert!(find_flag("ambient").is_none()); // not a Cepton flag bit
+
+        let mask = FlagMask(0x21);
+        assert!(mask.matches(0x01));
+        assert!(!mask.matches(0x04));
+        assert_eq!(mask.to_string(), "saturated, no_return");
+        assert_eq!(FlagMask::default().to_string(), "none");
+
+        let mut counts = FlagCounts::default();
+        for flags in [0x00, 0x01, 0x21, 0x80] {
+            counts.push(flags);
+        }
+        let seen: Vec<(&str, usize)> = counts.seen().map(|(flag, count)| (flag.name, count)).collect();
+        assert_eq!(seen, [("saturated", 2), ("no_return", 1), ("blocked", 1)]);
+    }
+}
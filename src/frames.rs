@@ -0,0 +1,354 @@
+//! Frame segmentation - splits each sensor's packet stream into scan frames,
+//! and the writer that puts every frame into its own output files
+
+use crate::cepton::{ChannelKey, Point};
+use crate::export::{ExportSummary, PointWriter, sensor_output_path};
+use anyhow::Result;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::net::SocketAddr;
+
+/// How frame boundaries are found
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FrameMode {
+    Period(u64), // fixed frame period in microseconds of sensor time
+    Wrap,        // the scan pattern jumps back to its start
+}
+
+impl fmt::Display for FrameMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FrameMode::Period(us) => write!(f, "every {} ms", *us as f64 / 1000.0),
+            FrameMode::Wrap => write!(f, "scan pattern wrap-around"),
+        }
+    }
+}
+
+/// Smallest azimuth jump between two packets that counts as a wrap-around, in degrees
+const MIN_WRAP_DEGREES: f64 = 5.0;
+
+/// Frames a sensor may fall behind the frame reached before it is taken for silent
+const MAX_FRAME_LAG: u64 = 3;
+
+/// Assigns a frame index to every packet. Frames are counted per sensor; a sensor that
+/// shows up later starts at the frame the others have reached, so indices stay comparable.
+/// A sensor more than MAX_FRAME_LAG frames behind has gone silent: it no longer holds
+/// frames open, and when it sends again it starts over like a sensor showing up late.
+/// The same packets in the same order always get the same frames, so the scan and the
+/// extraction agree.
+#[derive(Debug)]
+pub struct FrameSplitter {
+    mode: FrameMode,
+    sensors: HashMap<SocketAddr, SensorFrames>,
+}
+
+/// Frame state of one sensor
+#[derive(Debug, Clone, Copy)]
+struct SensorFrames {
+    first_frame: u64,     // frame of the first packet
+    origin: u64,          // sensor time of the first packet (us)
+    frame: u64,           // current frame; never decreases
+    azimuth: Option<f64>, // mean azimuth of the previous packet
+    span: (f64, f64),     // azimuth range swept in the current frame
+}
+
+impl FrameSplitter {
+    pub fn new(mode: FrameMode) -> Self {
+        FrameSplitter { mode, sensors: HashMap::new() }
+    }
+
+    /// True when `packet_frame` needs the packet's azimuth
+    pub fn needs_azimuth(&self) -> bool {
+        self.mode == FrameMode::Wrap
+    }
+
+    /// Frame of the next packet of `sensor`. `timestamp_us` is the STDV header timestamp,
+    /// `azimuth` the mean azimuth of the packet's points in degrees (Wrap mode only).
+    pub fn packet_frame(&mut self, sensor: SocketAddr, timestamp_us: u64, azimuth: Option<f64>) -> u64 {
+        let reached = self.reached();
+        let start = SensorFrames {
+            first_frame: reached,
+            origin: timestamp_us,
+            frame: reached,
+            azimuth: None,
+            span: (0.0, 0.0),
+        };
+        let state = self.sensors.entry(sensor).or_insert(start);
+        if state.frame + MAX_FRAME_LAG < reached {
+            *state = start;
+        }
+
+        match self.mode {
+            FrameMode::Period(period) => {
+                // Late packets stay in the current frame
+                let frame = state.first_frame + timestamp_us.saturating_sub(state.origin) / period;
+                state.frame = state.frame.max(frame);
+            }
+            FrameMode::Wrap => {
+                let Some(azimuth) = azimuth else {
+                    return state.frame;
+                };
+                let (min, max) = state.span;
+                match state.azimuth {
+                    // A jump of more than half the swept range starts the next sweep
+                    Some(previous) if (azimuth - previous).abs() > ((max - min) / 2.0).max(MIN_WRAP_DEGREES) => {
+                        state.frame += 1;
+                        state.span = (azimuth, azimuth);
+                    }
+                    Some(_) => state.span = (min.min(azimuth), max.max(azimuth)),
+                    None => state.span = (azimuth, azimuth),
+                }
+                state.azimuth = Some(azimuth);
+            }
+        }
+
+        state.frame
+    }
+
+    /// Highest frame any sensor has reached
+    fn reached(&self) -> u64 {
+        self.sensors.values().map(|s| s.frame).max().unwrap_or(0)
+    }
+
+    /// Frames below this are over: every sensor still sending has moved past them,
+    /// and a sensor that shows up later, or again, starts at the highest frame reached
+    pub fn complete_below(&self) -> u64 {
+        let reached = self.reached();
+        let slowest = self.sensors.values().map(|s| s.frame).min().unwrap_or(0);
+        slowest.max(reached.saturating_sub(MAX_FRAME_LAG))
+    }
+}
+
+/// Circular mean of the horizontal angles of a packet's points in degrees
+/// (0 = straight ahead, +Y), so a packet straddling ±180 averages to about 180;
+/// points at the origin carry no direction and are skipped
+pub fn mean_azimuth(points: impl Iterator<Item = (f64, f64)>) -> Option<f64> {
+    let (sin, cos, count) = points.filter(|&(x, y)| x != 0.0 || y != 0.0).fold(
+        (0.0, 0.0, 0usize),
+        |(sin, cos, count), (x, y)| {
+            let r = x.hypot(y);
+            (sin + x / r, cos + y / r, count + 1)
+        },
+    );
+    (count > 0).then(|| sin.atan2(cos).to_degrees())
+}
+
+/// "out.csv" + frame 12 -> "out_Frame_12.csv"
+pub fn frame_output_path(output_path: &str, frame: u64) -> String {
+    sensor_output_path(output_path, &format!("Frame_{}", frame))
+}
+
+/// Writes each frame through its own writer, created by `open` with the frame's output path.
+/// A frame is finished once `frames_complete` reports that the splitter has moved every
+/// sensor past it, so only the frames in progress are open. A point of a frame that was
+/// already written goes into a continuation file, "out_Frame_12_part2.csv".
+pub struct FrameFiles<F> {
+    output_path: String,
+    open: F,
+    writers: BTreeMap<u64, Box<dyn PointWriter>>,
+    finished: u64,              // frames below this are written
+    parts: HashMap<u64, usize>, // files opened for each frame
+    summary: ExportSummary,
+}
+
+impl<F> FrameFiles<F>
+where
+    F: FnMut(&str) -> Box<dyn PointWriter>,
+{
+    pub fn new(output_path: &str, open: F) -> Self {
+        FrameFiles {
+            output_path: output_path.to_string(),
+            open,
+            writers: BTreeMap::new(),
+            finished: 0,
+            parts: HashMap::new(),
+            summary: ExportSummary::default(),
+        }
+    }
+
+    fn finish_frame(&mut self, writer: Box<dyn PointWriter>) -> Result<()> {
+        let summary = writer.finish()?;
+        self.summary.files.extend(summary.files);
+        self.summary.split_sheets.extend(summary.split_sheets);
+        Ok(())
+    }
+}
+
+impl<F> PointWriter for FrameFiles<F>
+where
+    F: FnMut(&str) -> Box<dyn PointWriter>,
+{
+    fn write_point(&mut self, key: ChannelKey, point: &Point) -> Result<()> {
+        let frame = point.frame;
+        let writer = match self.writers.get_mut(&frame) {
+            Some(writer) => writer,
+            None => {
+                // A frame that was already written continues in its next part
+                let part = self.parts.entry(frame).or_insert(0);
+                *part += 1;
+                let mut path = frame_output_path(&self.output_path, frame);
+                if *part > 1 {
+                    path = sensor_output_path(&path, &format!("part{}", part));
+                }
+                let writer = (self.open)(&path);
+                self.writers.entry(frame).or_insert(writer)
+            }
+        };
+        writer.write_point(key, point)
+    }
+
+    fn frames_complete(&mut self, frame: u64) -> Result<()> {
+        self.finished = self.finished.max(frame);
+        while let Some(entry) = self.writers.first_entry()
+            && *entry.key() < self.finished
+        {
+            let writer = entry.remove();
+            self.finish_frame(writer)?;
+        }
+        Ok(())
+    }
+
+    fn finish(mut self: Box<Self>) -> Result<ExportSummary> {
+        while let Some((_, writer)) = self.writers.pop_first() {
+            self.finish_frame(writer)?;
+        }
+        Ok(self.summary)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::csv_exporter::{CsvOptions, CsvWriter};
+    use crate::export::Layout;
+    use crate::pcap_reader::SensorStats;
+
+    #[test]
+    fn test_period_frames() {
+        let a: SocketAddr = "192.168.1.201:8808".parse().unwrap();
+        let b: SocketAddr = "192.168.1.202:8808".parse().unwrap();
+        let mut splitter = FrameSplitter::new(FrameMode::Period(100_000));
+
+        assert_eq!(splitter.packet_frame(a, 5_000_000, None), 0);
+        assert_eq!(splitter.packet_frame(a, 5_099_999, None), 0);
+        assert_eq!(splitter.packet_frame(a, 5_100_000, None), 1);
+        assert_eq!(splitter.packet_frame(a, 5_050_000, None), 1); // late packet
+        assert_eq!(splitter.packet_frame(a, 5_350_000, None), 3);
+
+        // A sensor joining later continues from the frame already reached
+        assert_eq!(splitter.packet_frame(b, 42, None), 3);
+        assert_eq!(splitter.packet_frame(b, 100_042, None), 4);
+    }
+
+    #[test]
+    fn test_wrap_frames() {
+        let sensor: SocketAddr = "192.168.1.201:8808".parse().unwrap();
+        let mut splitter = FrameSplitter::new(FrameMode::Wrap);
+        assert!(splitter.needs_azimuth());
+
+        // Three left-to-right sweeps in 2 degree steps, with a little jitter
+        let sweep: Vec<f64> = (0..=30).map(|i| -30.0 + 2.0 * i as f64 - (i % 2) as f64 * 2.5).collect();
+        let frames: Vec<u64> = sweep
+            .iter()
+            .chain(&sweep)
+            .chain(&sweep[..10])
+            .map(|&azimuth| splitter.packet_frame(sensor, 0, Some(azimuth)))
+            .collect();
+        assert!(frames[..31].iter().all(|&frame| frame == 0));
+        assert!(frames[31..62].iter().all(|&frame| frame == 1));
+        assert!(frames[62..].iter().all(|&frame| frame == 2));
+
+        // Packets without a usable point stay in the current frame
+        assert_eq!(splitter.packet_frame(sensor, 0, None), 2);
+
+        assert_eq!(mean_azimuth([(1.0, 1.0), (0.0, 0.0), (-1.0, 1.0)].into_iter()), Some(0.0));
+        assert_eq!(mean_azimuth([(0.0, 0.0)].into_iter()), None);
+
+        // Straight behind: the points straddle ±180 and must not average to straight ahead
+        let behind = mean_azimuth([(0.1, -1.0), (-0.1, -1.0), (0.05, -1.0)].into_iter()).unwrap();
+        assert!(behind.abs() > 178.0, "{}", behind);
+    }
+
+    #[test]
+    fn test_silent_sensor() {
+        let a: SocketAddr = "192.168.1.201:8808".parse().unwrap();
+        let b: SocketAddr = "192.168.1.202:8808".parse().unwrap();
+        let mut splitter = FrameSplitter::new(FrameMode::Period(100_000));
+        let sensor = SocketAddr::new([0, 0, 0, 0].into(), 0);
+        let options = CsvOptions { layout: Layout::Merged, ..Default::default() };
+        let open = move |path: &str| -> Box<dyn PointWriter> {
+            Box::new(CsvWriter::new(path, &options, &BTreeMap::from([(sensor, SensorStats::default())])))
+        };
+        let mut writer = Box::new(FrameFiles::new("test_silent.csv", open));
+
+        // b stops sending after its first frame; a goes on for 20 frames
+        assert_eq!(splitter.packet_frame(b, 0, None), 0);
+        let mut most_open = 0;
+        for i in 0..200 {
+            let frame = splitter.packet_frame(a, i * 10_000, None);
+            writer.frames_complete(splitter.complete_below()).unwrap();
+            let point = Point { frame, ..Default::default() };
+            writer.write_point(ChannelKey { sensor, channel: 0 }, &point).unwrap();
+            most_open = most_open.max(writer.writers.len());
+        }
+        assert_eq!(splitter.complete_below(), 19 - MAX_FRAME_LAG);
+        assert!(most_open <= MAX_FRAME_LAG as usize + 1, "{} frames open", most_open);
+
+        // Back again, b continues from the frame a has reached
+        assert_eq!(splitter.packet_frame(b, 50_000_000, None), 19);
+        assert_eq!(splitter.complete_below(), 19);
+
+        let summary = writer.finish().unwrap();
+        for file in &summary.files {
+            let _ = std::fs::remove_file(file);
+        }
+        assert_eq!(summary.files.len(), 20);
+    }
+
+    #[test]
+    fn test_frame_files() {
+        let sensor: SocketAddr = "192.168.1.201:8808".parse().unwrap();
+        let options = CsvOptions { layout: Layout::Merged, ..Default::default() };
+        let open = move |path: &str| -> Box<dyn PointWriter> {
+            Box::new(CsvWriter::new(path, &options, &BTreeMap::from([(sensor, SensorStats::default())])))
+        };
+
+        let mut writer = Box::new(FrameFiles::new("test_frames.csv", open));
+        let write = |writer: &mut FrameFiles<_>, frame| {
+            let point = Point { frame, ..Default::default() };
+            writer.write_point(ChannelKey { sensor, channel: 0 }, &point).unwrap();
+        };
+
+        // One sensor is at frame 3, another sensor whose points were all filtered is still at 1
+        for frame in [0, 0, 1, 3] {
+            write(&mut writer, frame);
+        }
+        writer.frames_complete(1).unwrap();
+        assert_eq!(writer.writers.len(), 2); // frame 0 is written, 1 and 3 stay open
+
+        // The lagging sensor's first point still goes into frame 1
+        write(&mut writer, 1);
+        writer.frames_complete(3).unwrap();
+        assert_eq!(writer.writers.len(), 1);
+
+        // A point of a frame that was already written continues in a second part
+        write(&mut writer, 0);
+
+        let summary = writer.finish().unwrap();
+        let texts: Vec<String> = summary.files.iter().map(|file| std::fs::read_to_string(file).unwrap()).collect();
+        for file in &summary.files {
+            let _ = std::fs::remove_file(file);
+        }
+
+        assert_eq!(
+            summary.files,
+            vec![
+                "test_frames_Frame_0.csv",
+                "test_frames_Frame_1.csv",
+                "test_frames_Frame_0_part2.csv",
+                "test_frames_Frame_3.csv"
+            ]
+        );
+        assert_eq!(texts.iter().map(|text| text.lines().count()).collect::<Vec<_>>(), [3, 3, 2, 2]);
+    }
+}
@@ -0,0 +1,375 @@
+//! LAS 1.4 export - ASPRS point data record format 6 for GIS / mapping tools.
+//! Coordinates are stored as scaled integers relative to an offset taken from the
+//! first point of each file, so points can be streamed; the point count and bounds
+//! in the header are patched in place once the file is complete. Polar columns are
+//! stored as extra bytes, described by an Extra Bytes VLR after the header.
+
+use crate::cepton::{ChannelKey, Point};
+use crate::export::{ExportSummary, Layout, OpenFiles, OutputFiles, PointWriter};
+use anyhow::{Context, Result, bail};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufWriter, Seek, SeekFrom, Write};
+use std::net::SocketAddr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// LAS writer options
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LasOptions {
+    pub layout: Layout,
+    pub per_sensor_files: bool,
+    pub polar: bool, // add range, azimuth and elevation as extra bytes
+}
+
+const HEADER_SIZE: u16 = 375; // LAS 1.4 public header block
+const POINT_FORMAT: u8 = 6;
+const POINT_SIZE: u16 = 30;
+
+/// Extra bytes of the polar columns: name, description; each a 4-byte float
+const POLAR_EXTRA_BYTES: [(&str, &str); 3] = [
+    ("range", "Distance from the sensor (m)"),
+    ("azimuth", "Angle right of forward (deg)"),
+    ("elevation", "Angle above horizontal (deg)"),
+];
+const VLR_HEADER_SIZE: usize = 54;
+const EXTRA_BYTES_DESCRIPTOR_SIZE: usize = 192;
+const EXTRA_BYTES_FLOAT: u8 = 9;
+
+/// Coordinate resolution in meters; the sensor reports 0.5 cm steps
+const SCALE: f64 = 0.001;
+
+/// Global encoding: GPS time is Adjusted Standard GPS Time (bit 0),
+/// WKT coordinate system (bit 4, required for point formats 6-10)
+const GLOBAL_ENCODING: u16 = 0x0011;
+
+/// Unix time of the GPS epoch, 1980-01-06
+const GPS_EPOCH_UNIX: f64 = 315_964_800.0;
+
+/// GPS - UTC, unchanged since 2017-01-01
+const GPS_LEAP_SECONDS: f64 = 18.0;
+
+/// Streams points into one LAS file per channel, or into one merged cloud
+pub struct LasWriter {
+    outputs: OpenFiles<LasFile>,
+    polar: bool,
+    source_ids: BTreeMap<SocketAddr, u16>, // point source ID of each sensor, 1-based
+}
+
+/// One open output file
+struct LasFile {
+    writer: BufWriter<File>,
+    offset: [f64; 3],
+    min: [f64; 3],
+    max: [f64; 3],
+    points: u64,
+    polar: bool,
+}
+
+impl LasWriter {
+    /// Start an export to `output_path`; `sensors` are the sensors whose points will be written
+    pub fn new(output_path: &str, options: &LasOptions, sensors: impl Iterator<Item = SocketAddr>) -> Self {
+        let sensors: Vec<SocketAddr> = sensors.collect();
+        let files = OutputFiles::new(output_path, options.layout, options.per_sensor_files, sensors.iter().copied());
+
+        let mut source_ids: Vec<_> = sensors;
+        source_ids.sort();
+        source_ids.dedup();
+
+        LasWriter {
+            outputs: OpenFiles::new(files),
+            polar: options.polar,
+            source_ids: source_ids.into_iter().zip(1..).collect(),
+        }
+    }
+}
+
+/// Create a file with a placeholder header; the offset is the first point rounded to whole meters
+fn open(path: &str, point: &Point, polar: bool) -> Result<LasFile> {
+    let mut output = LasFile {
+        writer: BufWriter::new(File::create(path).with_context(|| format!("Failed to create file: {}", path))?),
+        offset: [point.x.round(), point.y.round(), point.z.round()],
+        min: [f64::MAX; 3],
+        max: [f64::MIN; 3],
+        points: 0,
+        polar,
+    };
+    let header = header(&output, creation_date(SystemTime::now()));
+    output.writer.write_all(&header)?;
+    if polar {
+        output.writer.write_all(&extra_bytes_vlr())?;
+    }
+
+    Ok(output)
+}
+
+impl PointWriter for LasWriter {
+    fn write_point(&mut self, key: ChannelKey, point: &Point) -> Result<()> {
+        let polar = self.polar;
+        let file = self.outputs.get_or_open(key, |path| open(path, point, polar))?;
+        let output = &mut file.output;
+
+        let xyz = [point.x, point.y, point.z];
+        let mut record = Vec::with_capacity(point_size(polar) as usize);
+        for (axis, value) in xyz.into_iter().enumerate() {
+            output.min[axis] = output.min[axis].min(value);
+            output.max[axis] = output.max[axis].max(value);
+
+            let scaled = ((value - output.offset[axis]) / SCALE).round();
+            if scaled < i32::MIN as f64 || scaled > i32::MAX as f64 {
+                bail!("Point too far from the LAS offset of {}: ({}, {}, {})", file.path, point.x, point.y, point.z);
+            }
+            record.extend_from_slice(&(scaled as i32).to_le_bytes());
+        }
+        output.points += 1;
+
+        // Reflectivity normalized to the 16 bit intensity range
+        record.extend_from_slice(&(point.reflectivity as u16 * 256).to_le_bytes());
+        record.push(0x11); // return 1 of 1
+        record.push(0); // classification flags, scanner channel, scan direction, edge of flight line
+        record.push(0); // classification: created, never classified
+        record.push(point.channel); // user data
+        record.extend_from_slice(&0i16.to_le_bytes()); // scan angle
+        record.extend_from_slice(&self.source_ids.get(&key.sensor).copied().unwrap_or(0).to_le_bytes());
+        record.extend_from_slice(&gps_time(point.capture_time_ns).to_le_bytes());
+        if polar {
+            for value in [point.range(), point.azimuth(), point.elevation()] {
+                record.extend_from_slice(&(value as f32).to_le_bytes());
+            }
+        }
+
+        output.writer
+            .write_all(&record)
+            .with_context(|| format!("Failed to write file: {}", file.path))
+    }
+
+    fn finish(self: Box<Self>) -> Result<ExportSummary> {
+        let created = creation_date(SystemTime::now());
+
+        let mut summary = ExportSummary::default();
+        for file in self.outputs.into_sorted() {
+            let context = || format!("Failed to write file: {}", file.path);
+            let output = file.output;
+
+            // Now that all points are known, fill the count and bounds into the header
+            let header = header(&output, created);
+            let mut handle = output.writer.into_inner().map_err(|e| e.into_error()).with_context(context)?;
+            handle.seek(SeekFrom::Start(0)).with_context(context)?;
+            handle.write_all(&header).with_context(context)?;
+
+            summary.files.push(file.path);
+        }
+
+        Ok(summary)
+    }
+}
+
+/// LAS 1.4 public header block
+fn header(output: &LasFile, (year, day): (u16, u16)) -> Vec<u8> {
+    fn text(header: &mut Vec<u8>, s: &str, len: usize) {
+        let mut field = s.as_bytes().to_vec();
+        field.resize(len, 0);
+        header.extend_from_slice(&field);
+    }
+
+    // Bounds of an empty file are all zero
+    let (min, max) = match output.points {
+        0 => ([0.0; 3], [0.0; 3]),
+        _ => (output.min, output.max),
+    };
+
+    let (vlrs, vlr_size) = match output.polar {
+        true => (1u32, extra_bytes_vlr().len() as u32),
+        false => (0, 0),
+    };
+
+    let mut header = Vec::with_capacity(HEADER_SIZE as usize);
+    header.extend_from_slice(b"LASF");
+    header.extend_from_slice(&0u16.to_le_bytes()); // file source ID
+    header.extend_from_slice(&GLOBAL_ENCODING.to_le_bytes());
+    header.extend_from_slice(&[0; 16]); // project ID (GUID)
+    header.extend_from_slice(&[1, 4]); // version
+    text(&mut header, "OTHER", 32); // system identifier
+    text(&mut header, concat!(env!("CARGO_PKG_NAME"), " ", env!("CARGO_PKG_VERSION")), 32);
+    header.extend_from_slice(&day.to_le_bytes());
+    header.extend_from_slice(&year.to_le_bytes());
+    header.extend_from_slice(&HEADER_SIZE.to_le_bytes());
+    header.extend_from_slice(&(HEADER_SIZE as u32 + vlr_size).to_le_bytes()); // offset to point data
+    header.extend_from_slice(&vlrs.to_le_bytes());
+    header.push(POINT_FORMAT);
+    header.extend_from_slice(&point_size(output.polar).to_le_bytes());
+    header.extend_from_slice(&[0; 4 + 5 * 4]); // legacy point counts, unused by format 6
+    for _ in 0..3 {
+        header.extend_from_slice(&SCALE.to_le_bytes());
+    }
+    for offset in output.offset {
+        header.extend_from_slice(&offset.to_le_bytes());
+    }
+    for (max, min) in max.iter().zip(&min) {
+        header.extend_from_slice(&max.to_le_bytes());
+        header.extend_from_slice(&min.to_le_bytes());
+    }
+    header.extend_from_slice(&0u64.to_le_bytes()); // start of waveform data
+    header.extend_from_slice(&0u64.to_le_bytes()); // start of first EVLR
+    header.extend_from_slice(&0u32.to_le_bytes()); // number of EVLRs
+    header.extend_from_slice(&output.points.to_le_bytes());
+    header.extend_from_slice(&output.points.to_le_bytes()); // points by return: all are first returns
+    header.extend_from_slice(&[0; 14 * 8]);
+
+    header
+}
+
+/// Bytes per point record, with or without the polar extra bytes
+fn point_size(polar: bool) -> u16 {
+    match polar {
+        true => POINT_SIZE + 4 * POLAR_EXTRA_BYTES.len() as u16,
+        false => POINT_SIZE,
+    }
+}
+
+/// Extra Bytes VLR (user "LASF_Spec", record 4) describing the polar columns
+fn extra_bytes_vlr() -> Vec<u8> {
+    fn text(vlr: &mut Vec<u8>, s: &str, len: usize) {
+        let mut field = s.as_bytes().to_vec();
+        field.resize(len, 0);
+        vlr.extend_from_slice(&field);
+    }
+
+    let length = POLAR_EXTRA_BYTES.len() * EXTRA_BYTES_DESCRIPTOR_SIZE;
+    let mut vlr = Vec::with_capacity(VLR_HEADER_SIZE + length);
+    vlr.extend_from_slice(&0u16.to_le_bytes()); // reserved
+    text(&mut vlr, "LASF_Spec", 16);
+    vlr.extend_from_slice(&4u16.to_le_bytes()); // record ID: extra bytes
+    vlr.extend_from_slice(&(length as u16).to_le_bytes());
+    text(&mut vlr, "Polar coordinates", 32);
+
+    for (name, description) in POLAR_EXTRA_BYTES {
+        vlr.extend_from_slice(&[0, 0, EXTRA_BYTES_FLOAT, 0]); // reserved, data type, options: no scale, offset, limits
+        text(&mut vlr, name, 32);
+        vlr.extend_from_slice(&[0; 4 + 5 * 24]); // unused, no data, min, max, scale, offset
+        text(&mut vlr, description, 32);
+    }
+
+    vlr
+}
+
+/// Adjusted Standard GPS Time (GPS seconds - 1e9) of a Unix capture time
+fn gps_time(unix_ns: u64) -> f64 {
+    let secs = (unix_ns / 1_000_000_000) as f64 + (unix_ns % 1_000_000_000) as f64 / 1e9;
+    secs - GPS_EPOCH_UNIX + GPS_LEAP_SECONDS - 1e9
+}
+
+/// (year, day of year) of a UTC time, for the header's file creation date
+fn creation_date(time: SystemTime) -> (u16, u16) {
+    let days = time.duration_since(UNIX_EPOCH).map(|d| d.as_secs() / 86400).unwrap_or(0);
+
+    let mut year = 1970;
+    let mut day = days;
+    loop {
+        let leap = year % 4 == 0 && (year % 100 != 0 || year % 400 == 0);
+        let len = if leap { 366 } else { 365 };
+        if day < len {
+            return (year as u16, day as u16 + 1);
+        }
+        day -= len;
+        year += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn f64_at(bytes: &[u8], offset: usize) -> f64 {
+        f64::from_le_bytes(bytes[offset..offset + 8].try_into().unwrap())
+    }
+
+    #[test]
+    fn test_las_export() {
+        let a: SocketAddr = "192.168.1.201:8808".parse().unwrap();
+        let b: SocketAddr = "192.168.1.202:8808".parse().unwrap();
+        let first = Point {
+            x: 10.4,
+            y: -2.0,
+            z: 0.25,
+            reflectivity: 40,
+            channel: 3,
+            capture_time_ns: 1_700_000_000_250_000_000,
+            ..Default::default()
+        };
+        let second = Point { x: 12.005, y: -3.5, z: 1.0, channel: 7, ..first.clone() };
+
+        let options = LasOptions { layout: Layout::Merged, ..Default::default() };
+        let mut writer = Box::new(LasWriter::new("test_merged.las", &options, [a, b].into_iter()));
+        writer.write_point(ChannelKey { sensor: a, channel: 3 }, &first).unwrap();
+        writer.write_point(ChannelKey { sensor: b, channel: 7 }, &second).unwrap();
+        let summary = writer.finish().unwrap();
+
+        assert_eq!(summary.files, vec!["test_merged.las"]);
+        let bytes = std::fs::read("test_merged.las").unwrap();
+        let _ = std::fs::remove_file("test_merged.las");
+
+        assert_eq!(bytes.len(), 375 + 2 * 30);
+        assert_eq!(&bytes[0..4], b"LASF");
+        assert_eq!(&bytes[24..26], &[1, 4]);
+        assert_eq!(bytes[104], 6);
+        assert_eq!(u64::from_le_bytes(bytes[247..255].try_into().unwrap()), 2);
+        assert_eq!(f64_at(&bytes, 131), 0.001);
+        assert_eq!(f64_at(&bytes, 155), 10.0); // x offset
+        assert_eq!((f64_at(&bytes, 179), f64_at(&bytes, 187)), (12.005, 10.4)); // max x, min x
+        assert_eq!((f64_at(&bytes, 211), f64_at(&bytes, 219)), (1.0, 0.25)); // max z, min z
+
+        let point = &bytes[375..405];
+        assert_eq!(i32::from_le_bytes(point[0..4].try_into().unwrap()), 400);
+        assert_eq!(u16::from_le_bytes(point[12..14].try_into().unwrap()), 40 * 256);
+        assert_eq!(point[17], 3); // user data: channel
+        assert_eq!(u16::from_le_bytes(point[20..22].try_into().unwrap()), 1); // point source: first sensor
+        assert!((f64_at(point, 22) - 384_035_218.25).abs() < 1e-6);
+
+        let point = &bytes[405..435];
+        assert_eq!(i32::from_le_bytes(point[0..4].try_into().unwrap()), 2005);
+        assert_eq!(point[17], 7);
+        assert_eq!(u16::from_le_bytes(point[20..22].try_into().unwrap()), 2);
+    }
+
+    #[test]
+    fn test_las_polar() {
+        let sensor: SocketAddr = "192.168.1.201:8808".parse().unwrap();
+        let point = Point { x: 3.0, y: 4.0, z: 0.0, ..Default::default() };
+
+        let options = LasOptions { layout: Layout::Merged, polar: true, ..Default::default() };
+        let mut writer = Box::new(LasWriter::new("test_polar.las", &options, [sensor].into_iter()));
+        writer.write_point(ChannelKey { sensor, channel: 1 }, &point).unwrap();
+        writer.finish().unwrap();
+
+        let bytes = std::fs::read("test_polar.las").unwrap();
+        let _ = std::fs::remove_file("test_polar.las");
+
+        let data_offset = 375 + 54 + 3 * 192;
+        assert_eq!(bytes.len(), data_offset + 42);
+        assert_eq!(u32::from_le_bytes(bytes[96..100].try_into().unwrap()), data_offset as u32);
+        assert_eq!(u32::from_le_bytes(bytes[100..104].try_into().unwrap()), 1); // number of VLRs
+        assert_eq!(u16::from_le_bytes(bytes[105..107].try_into().unwrap()), 42);
+
+        let vlr = &bytes[375..];
+        assert_eq!(&vlr[2..11], b"LASF_Spec");
+        assert_eq!(u16::from_le_bytes(vlr[18..20].try_into().unwrap()), 4);
+        assert_eq!(u16::from_le_bytes(vlr[20..22].try_into().unwrap()), 3 * 192);
+        assert_eq!(vlr[54 + 2], 9); // float
+        assert_eq!(&vlr[54 + 4..54 + 9], b"range");
+        assert_eq!(&vlr[54 + 192 + 4..54 + 192 + 11], b"azimuth");
+
+        let extra = &bytes[data_offset + 30..];
+        assert_eq!(f32::from_le_bytes(extra[0..4].try_into().unwrap()), 5.0);
+        assert!((f32::from_le_bytes(extra[4..8].try_into().unwrap()) - 36.869_9).abs() < 1e-3);
+        assert_eq!(f32::from_le_bytes(extra[8..12].try_into().unwrap()), 0.0);
+    }
+
+    #[test]
+    fn test_creation_date() {
+        let at = |secs| creation_date(UNIX_EPOCH + Duration::from_secs(secs));
+        assert_eq!(at(0), (1970, 1));
+        assert_eq!(at(951_782_400), (2000, 60)); // 2000-02-29
+        assert_eq!(at(1_735_603_200), (2024, 366)); // 2024-12-31
+    }
+}
@@ -1,141 +1,431 @@
+mod capture;
 mod cepton;
+mod cli;
+mod columns;
+mod diagnostics;
+mod error;
+mod flags;
+mod frames;
+mod net;
 mod pcap_reader;
+mod region;
+mod time_window;
+mod transform;
+mod export;
 mod excel_exporter;
-
-use anyhow::Result;
+mod csv_exporter;
+mod pcd_exporter;
+mod ply_exporter;
+mod las_exporter;
+mod parquet_exporter;
+mod npy_exporter;
+
+use anyhow::{Context, Result};
+use error::ConvertError;
+use export::PointWriter;
 use indicatif::{ProgressBar, ProgressStyle};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::io::{self, Write};
+use std::net::SocketAddr;
 use std::path::Path;
+use std::process::ExitCode;
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    // No arguments: fall back to the interactive wizard
+    let result = if args.is_empty() {
+        run_wizard()
+    } else {
+        match cli::parse_args(&args) {
+            Ok(cli::Command::Help) => {
+                println!("{}", cli::USAGE);
+                Ok(())
+            }
+            Ok(cli::Command::Run(options)) => run_cli(*options),
+            Err(e) => {
+                eprintln!("Error: {}\n\n{}", e, cli::USAGE);
+                return ExitCode::from(e.exit_code());
+            }
+        }
+    };
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {:#}", e);
+            ExitCode::from(error::exit_code_for(&e))
+        }
+    }
+}
 
-fn main() -> Result<()> {
+fn print_banner() {
     println!("=======================================================");
     println!("  Cepton LiDAR PCAP to Excel Converter");
     println!("  Extract XYZ coordinates by channel");
     println!("=======================================================\n");
+}
+
+/// Read one trimmed line from stdin after printing a prompt
+fn prompt(message: &str) -> Result<String> {
+    print!("{}", message);
+    io::stdout().flush()?;
+
+    let mut input = String::new();
+    io::stdin().read_line(&mut input)?;
+    Ok(input.trim().to_string())
+}
+
+/// Interactive mode: every setting is asked on stdin
+fn run_wizard() -> Result<()> {
+    print_banner();
 
     // Select parse mode
     println!("[Step 0/5] Select data format:");
+    println!("  0. Auto-detect  (read point size from each packet header)");
     println!("  1. Normal mode  (10 bytes/point, 144 points/packet)");
     println!("  2. Debug mode   (17 bytes/point, 72 points/packet)");
-    print!("\nYour selection [1]: ");
-    io::stdout().flush()?;
-
-    let mut mode_input = String::new();
-    io::stdin().read_line(&mut mode_input)?;
-    let mode_input = mode_input.trim();
 
-    let parse_mode = match mode_input {
+    let parse_mode = match prompt("\nYour selection [0]: ")?.as_str() {
+        "1" => {
+            println!("Using Normal mode (10 bytes/point)");
+            Some(cepton::ParseMode::Normal)
+        }
         "2" => {
             println!("Using Debug mode (17 bytes/point)");
-            cepton::ParseMode::Debug
+            Some(cepton::ParseMode::Debug)
         }
         _ => {
-            println!("Using Normal mode (10 bytes/point)");
-            cepton::ParseMode::Normal
+            println!("Using auto-detection");
+            None
         }
     };
 
     // Get input file path (simple stdin read)
-    print!("\nEnter PCAP file path [ch_28 (1).pcap]: ");
-    io::stdout().flush()?;
-
-    let mut input = String::new();
-    io::stdin().read_line(&mut input)?;
-    let pcap_file = input.trim();
-
+    let pcap_file = prompt("\nEnter PCAP file path [ch_28 (1).pcap]: ")?;
     let pcap_file = if pcap_file.is_empty() {
-        "ch_28 (1).pcap"
+        "ch_28 (1).pcap".to_string()
     } else {
         pcap_file
     };
 
     println!("Using file: {}", pcap_file);
+    check_input(&pcap_file)?;
+
+    let mut options = cli::CliOptions {
+        input: pcap_file,
+        mode: parse_mode,
+        channels: String::from("all"),
+        output: None,
+        overwrite: true,
+        filter: pcap_reader::PacketFilter::default(),
+        region: region::RegionFilter::default(),
+        transforms: transform::SensorTransforms::default(),
+        transform_files: Vec::new(),
+        drop_flags: flags::FlagMask::default(),
+        per_sensor_files: false,
+        columns: columns::ColumnOptions::default(),
+        split: excel_exporter::SplitMode::default(),
+        format: None,
+        layout: export::Layout::default(),
+        pcd_encoding: pcd_exporter::PcdEncoding::default(),
+        ply_encoding: ply_exporter::PlyEncoding::default(),
+        frames: None,
+        frame_range: None,
+        frame_files: false,
+    };
+
+    let report = scan(&options.input, options.mode, &options.filter, options.frames)?;
+    let channels = sorted_channels(&report.channel_counts);
+
+    // Let user select channels
+    println!("[Step 2/5] Select channels to extract:");
+    println!("  Options:");
+    println!("    - Enter channel numbers separated by commas (e.g., 0,5,10)");
+    println!("    - Enter 'all' to extract all channels");
+    println!("    - Enter a range (e.g., 0-10)");
+
+    options.channels = prompt("\nYour selection: ")?;
+    let selected_channels = cli::parse_channel_spec(&options.channels, &channels)?;
+
+    let output_file = cli::default_output_path(&options.input, options.output_format());
+    extract_and_export(&options, &report, &selected_channels, &output_file)?;
 
-    if !Path::new(&pcap_file).exists() {
-        anyhow::bail!("File not found: {}", pcap_file);
+    prompt("Press Enter to exit...")?;
+
+    Ok(())
+}
+
+/// Non-interactive mode: all settings come from the command line
+fn run_cli(mut options: cli::CliOptions) -> Result<()> {
+    print_banner();
+
+    println!("Using file: {}", options.input);
+    check_input(&options.input)?;
+    options.transforms = cli::resolve_transforms(&options)?;
+
+    let output_file = options
+        .output
+        .clone()
+        .unwrap_or_else(|| cli::default_output_path(&options.input, options.output_format()));
+
+    // Per-channel, per-sensor and per-frame outputs are checked once the scan is done;
+    // an .npz bundle always is the output file
+    let format = options.output_format();
+    let writes_output_file = !options.frame_files
+        && (format == export::OutputFormat::Npz
+            || (!options.per_sensor_files
+                && (format == export::OutputFormat::Xlsx || options.layout == export::Layout::Merged)));
+
+    if writes_output_file && Path::new(&output_file).exists() && !options.overwrite {
+        return Err(ConvertError::Usage(format!(
+            "Output file already exists: {} (use --yes to overwrite)",
+            output_file
+        ))
+        .into());
     }
 
+    let report = scan(&options.input, options.mode, &options.filter, options.frames)?;
+    let channels = sorted_channels(&report.channel_counts);
+
+    println!("[Step 2/5] Selecting channels: {}", options.channels);
+    let selected_channels = cli::parse_channel_spec(&options.channels, &channels)?;
+
+    extract_and_export(&options, &report, &selected_channels, &output_file)
+}
+
+fn check_input(pcap_file: &str) -> Result<()> {
+    if !Path::new(pcap_file).exists() {
+        return Err(io::Error::from(io::ErrorKind::NotFound))
+            .with_context(|| format!("File not found: {}", pcap_file));
+    }
+    Ok(())
+}
+
+fn sorted_channels(channel_stats: &HashMap<u8, usize>) -> Vec<u8> {
+    let mut channels: Vec<_> = channel_stats.keys().cloned().collect();
+    channels.sort();
+    channels
+}
+
+/// Step 1: scan the file and print per-sensor and per-channel point counts
+fn scan(
+    pcap_file: &str,
+    forced_mode: Option<cepton::ParseMode>,
+    filter: &pcap_reader::PacketFilter,
+    frame_mode: Option<frames::FrameMode>,
+) -> Result<pcap_reader::ScanReport> {
     println!("\n[Step 1/5] Scanning PCAP file for channels...");
     println!("(This may take a moment for large files...)");
 
     // Scan file to get channel statistics
-    let channel_stats = pcap_reader::scan_channels(&pcap_file, parse_mode)?;
+    let report = pcap_reader::scan_channels(pcap_file, forced_mode, filter, frame_mode)?;
+
+    // Display sensor endpoints
+    if !report.sensors.is_empty() {
+        println!("\nFound {} sensor endpoint(s):\n", report.sensors.len());
+        for (addr, stats) in &report.sensors {
+            let marker = if stats.matched { "" } else { "  (filtered out)" };
+            println!("  {:<40} {:8} packets {:10} points{}", addr.to_string(), stats.packets, stats.points, marker);
+        }
+    }
+    if !filter.is_empty() {
+        println!("\nPacket filter: {}", filter);
+    }
+    if !filter.window.is_empty() {
+        println!("Skipped {} packet(s) outside the time window", report.outside_window);
+    }
 
+    // Display sequence integrity of the sensors that pass the filter
+    println!("\nData integrity:");
+    for (addr, stats) in report.sensors.iter().filter(|(_, stats)| stats.matched) {
+        let seq = &stats.sequence;
+        println!(
+            "  {:<40} lost {} of {} packets ({:.2}%, ~{} points), {} gap(s), {} duplicate(s), {} out of order, {} reset(s)",
+            addr.to_string(),
+            seq.lost,
+            seq.expected,
+            seq.loss_ratio() * 100.0,
+            stats.lost_points_estimate(),
+            seq.gaps,
+            seq.duplicates,
+            seq.out_of_order,
+            seq.resets,
+        );
+    }
+
+    // Display point layout detected from the packet headers
+    if let Some(detected) = report.detected_mode() {
+        println!("\nDetected point format: {}", detected);
+    }
+    if report.point_sizes.len() > 1 {
+        let sizes: Vec<String> = report.point_sizes.iter()
+            .map(|(size, count)| format!("{} bytes x {} packets", size, count))
+            .collect();
+        println!("Warning: mixed point sizes in capture: {}", sizes.join(", "));
+    }
+    if let Some(forced) = forced_mode
+        && report.mode_mismatches > 0
+    {
+        println!(
+            "Warning: {} packet(s) announce a different point size; forcing {} as requested",
+            report.mode_mismatches, forced
+        );
+    }
+    if report.rejected_packets > 0 {
+        println!(
+            "Warning: skipped {} packet(s) with an unknown point size or point data larger than the payload",
+            report.rejected_packets
+        );
+    }
+
+    let channel_stats = &report.channel_counts;
     if channel_stats.is_empty() {
-        anyhow::bail!("No valid STDV packets found in the file");
+        return Err(ConvertError::NoStdvPackets.into());
     }
 
     // Display channel statistics
     println!("\nFound {} channels:\n", channel_stats.len());
-    let mut channels: Vec<_> = channel_stats.keys().cloned().collect();
-    channels.sort();
-
-    for channel in &channels {
-        let count = channel_stats[channel];
+    for channel in sorted_channels(channel_stats) {
+        let count = channel_stats[&channel];
         println!("  Channel {:2}: {:8} points", channel, count);
     }
 
     let total_points: usize = channel_stats.values().sum();
     println!("\n  Total:      {:8} points\n", total_points);
 
-    // Let user select channels
-    println!("[Step 2/5] Select channels to extract:");
-    println!("  Options:");
-    println!("    - Enter channel numbers separated by commas (e.g., 0,5,10)");
-    println!("    - Enter 'all' to extract all channels");
-    println!("    - Enter a range (e.g., 0-10)");
-    print!("\nYour selection: ");
-    io::stdout().flush()?;
-
-    let mut selection = String::new();
-    io::stdin().read_line(&mut selection)?;
-    let selection = selection.trim().to_lowercase();
-
-    let selected_channels: Vec<u8> = if selection == "all" {
-        channels.clone()
-    } else if selection.contains('-') && !selection.contains(',') {
-        // Range selection (e.g., "0-10")
-        let parts: Vec<&str> = selection.split('-').collect();
-        if parts.len() == 2 {
-            let start: u8 = parts[0].trim().parse()
-                .map_err(|_| anyhow::anyhow!("Invalid range start"))?;
-            let end: u8 = parts[1].trim().parse()
-                .map_err(|_| anyhow::anyhow!("Invalid range end"))?;
-            (start..=end).filter(|ch| channels.contains(ch)).collect()
-        } else {
-            anyhow::bail!("Invalid range format. Use: start-end (e.g., 0-10)");
+    // Display how many points carry each flag bit
+    if report.flag_counts.seen().next().is_some() {
+        println!("Point flags:\n");
+        for (flag, count) in report.flag_counts.seen() {
+            println!(
+                "  {:<14} {:8} points ({:.2}%)",
+                flag.name,
+                count,
+                count as f64 * 100.0 / total_points as f64
+            );
         }
-    } else {
-        // Comma-separated selection
-        selection
-            .split(',')
-            .filter_map(|s| s.trim().parse::<u8>().ok())
-            .filter(|ch| channels.contains(ch))
+        println!();
+    }
+
+    if let Some(mode) = frame_mode {
+        println!("  Frames:     {:8} ({})\n", report.frame_counts.len(), mode);
+    }
+
+    Ok(report)
+}
+
+/// Refuse to replace per-channel, per-sensor, per-frame or rollover files unless --yes was given
+fn check_overwrite(
+    options: &cli::CliOptions,
+    report: &pcap_reader::ScanReport,
+    sensors: &BTreeMap<SocketAddr, pcap_reader::SensorStats>,
+    selected_channels: &[u8],
+    output_file: &str,
+) -> Result<()> {
+    if options.overwrite {
+        return Ok(());
+    }
+
+    // Every frame gets its own set of files
+    let outputs: Vec<String> = if options.frame_files {
+        report.frame_counts.keys()
+            .filter(|frame| options.frame_range.as_ref().is_none_or(|range| range.contains(frame)))
+            .map(|&frame| frames::frame_output_path(output_file, frame))
             .collect()
+    } else {
+        vec![output_file.to_string()]
     };
 
-    if selected_channels.is_empty() {
-        anyhow::bail!("No valid channels selected");
+    let mut paths: Vec<String> = Vec::new();
+
+    for output in &outputs {
+        match options.output_format() {
+            export::OutputFormat::Xlsx => {
+                let base_files: Vec<String> = if options.per_sensor_files {
+                    let labels = export::sensor_labels(sensors.keys().copied());
+                    labels.values().map(|label| export::sensor_output_path(output, label)).collect()
+                } else {
+                    vec![output.clone()]
+                };
+
+                // Rollover workbooks the largest selected channel may need
+                let rows_per_sheet = excel_exporter::EXCEL_MAX_ROWS - 1;
+                let parts = match options.split {
+                    excel_exporter::SplitMode::Sheets => 1,
+                    excel_exporter::SplitMode::Workbooks => selected_channels.iter()
+                        .map(|ch| report.channel_counts[ch].div_ceil(rows_per_sheet))
+                        .max()
+                        .unwrap_or(1),
+                };
+
+                for base in &base_files {
+                    for part in 1..=parts.max(1) {
+                        paths.push(excel_exporter::part_output_path(base, part));
+                    }
+                }
+            }
+            export::OutputFormat::Npz => paths.push(output.clone()),
+            _ => {
+                let files = export::OutputFiles::new(
+                    output,
+                    options.layout,
+                    options.per_sensor_files,
+                    sensors.keys().copied(),
+                );
+                for &sensor in sensors.keys() {
+                    for &channel in selected_channels {
+                        paths.push(files.path(cepton::ChannelKey { sensor, channel }));
+                    }
+                }
+            }
+        }
     }
 
+    // The output file itself was already checked before the scan
+    for path in paths.iter().filter(|path| path.as_str() != output_file) {
+        if Path::new(path).exists() {
+            return Err(ConvertError::Usage(format!(
+                "Output file already exists: {} (use --yes to overwrite)",
+                path
+            ))
+            .into());
+        }
+    }
+
+    Ok(())
+}
+
+/// Steps 3-5: extract the selected channels and write the output files
+fn extract_and_export(
+    options: &cli::CliOptions,
+    report: &pcap_reader::ScanReport,
+    selected_channels: &[u8],
+    output_file: &str,
+) -> Result<()> {
     println!("\nSelected {} channel(s): {:?}", selected_channels.len(), selected_channels);
 
     // Calculate total points to extract
+    let channel_stats = &report.channel_counts;
     let points_to_extract: usize = selected_channels.iter()
         .map(|ch| channel_stats[ch])
         .sum();
 
     println!("Total points to extract: {}", points_to_extract);
 
-    // Extract points from selected channels
-    println!("\n[Step 3/5] Extracting XYZ coordinates...");
+    // Diagnostics of the sensors that pass the filter
+    let sensors: BTreeMap<_, _> = report.sensors.iter()
+        .filter(|(_, stats)| stats.matched)
+        .map(|(addr, stats)| (*addr, stats.clone()))
+        .collect();
 
-    let mut channel_points: HashMap<u8, Vec<cepton::Point>> = HashMap::new();
-    for &ch in &selected_channels {
-        channel_points.insert(ch, Vec::new());
-    }
+    check_overwrite(options, report, &sensors, selected_channels, output_file)?;
+
+    // Points go straight from the capture into the output files
+    let format = options.output_format();
+    println!("\n[Step 3/5] Extracting XYZ coordinates into {} output...", format);
 
+    let total_points: usize = channel_stats.values().sum();
     let pb = ProgressBar::new(total_points as u64);
     pb.set_style(
         ProgressStyle::default_bar()
@@ -143,30 +433,188 @@ fn main() -> Result<()> {
             .progress_chars("=>-")
     );
 
-    pcap_reader::extract_points(&pcap_file, &selected_channels, &mut channel_points, parse_mode, Some(&pb))?;
+    let mode = match (options.mode, report.detected_mode()) {
+        (Some(forced), _) => format!("{} (forced)", forced),
+        (None, Some(detected)) => format!("{} (auto-detected)", detected),
+        (None, None) => "Auto".to_string(),
+    };
+    let window = (!options.filter.window.is_empty()).then(|| options.filter.window.to_string());
+    let region = (!options.region.is_empty()).then(|| options.region.to_string());
+    let transform = (!options.transforms.is_empty()).then(|| options.transforms.describe(sensors.keys().copied()));
+    let source = export::SourceInfo { file: options.input.clone(), mode, window, region, transform };
+
+    // With --frame-files every frame is written through its own writer
+    let mut writer: Box<dyn PointWriter + '_> = if options.frame_files {
+        Box::new(frames::FrameFiles::new(output_file, |path| new_writer(options, path, &source, &sensors)))
+    } else {
+        new_writer(options, output_file, &source, &sensors)
+    };
+    let mut point_counts: HashMap<cepton::ChannelKey, usize> = HashMap::new();
+    let mut outside_region = 0;
+    let mut dropped_by_flags = 0;
+
+    let extraction = pcap_reader::Extraction {
+        channels: selected_channels,
+        forced_mode: options.mode,
+        filter: &options.filter,
+        frame_mode: options.frames,
+        transforms: &options.transforms,
+    };
+    pcap_reader::extract_points(
+        &options.input,
+        &extraction,
+        Some(&pb),
+        |key, point, complete_frames| {
+            // Frames no sensor can still reach are closed, even when this point is dropped
+            writer.frames_complete(complete_frames)?;
+
+            if let Some(range) = &options.frame_range
+                && !range.contains(&point.frame)
+            {
+                return Ok(());
+            }
+            if options.drop_flags.matches(point.flags) {
+                dropped_by_flags += 1;
+                return Ok(());
+            }
+            if !options.region.contains(point) {
+                outside_region += 1;
+                return Ok(());
+            }
+            *point_counts.entry(key).or_insert(0) += 1;
+            writer.write_point(key, point)
+        },
+    )?;
 
     pb.finish_with_message("Extraction complete!");
 
-    // Export to Excel
-    println!("\n[Step 4/5] Exporting to Excel...");
+    // Save the output files
+    println!("\n[Step 4/5] Saving {} file(s)...", format);
 
-    let output_file = pcap_file.replace(".pcap", "_xyz.xlsx");
-    excel_exporter::export_to_excel(&channel_points, &output_file)?;
+    let export = writer.finish()?;
 
     println!("\n✓ Export complete!");
-    println!("\nOutput file: {}", output_file);
+    for file in &export.files {
+        println!("\nOutput file: {}", file);
+    }
+
+    // Channels that did not fit into one worksheet
+    if !export.split_sheets.is_empty() {
+        println!("\nChannels over Excel's row limit ({} rows per sheet) were split:", excel_exporter::EXCEL_MAX_ROWS);
+        for split in &export.split_sheets {
+            println!("  {} ({} points):", split.sheet, split.points);
+            for (file, sheet) in &split.parts {
+                match options.split {
+                    excel_exporter::SplitMode::Sheets => println!("    - sheet {}", sheet),
+                    excel_exporter::SplitMode::Workbooks => println!("    - {} / sheet {}", file, sheet),
+                }
+            }
+        }
+    }
 
     // Summary
+    let mut keys: Vec<_> = point_counts.keys().collect();
+    keys.sort();
+    let multi_sensor = keys.iter().any(|key| key.sensor != keys[0].sensor);
+
     println!("\n=======================================================");
     println!("Summary:");
-    for &ch in &selected_channels {
-        println!("  Channel {}: {} points extracted", ch, channel_points[&ch].len());
+    for key in keys {
+        if multi_sensor {
+            println!("  {} Channel {}: {} points extracted", key.sensor, key.channel, point_counts[key]);
+        } else {
+            println!("  Channel {}: {} points extracted", key.channel, point_counts[key]);
+        }
+    }
+    if !options.drop_flags.is_empty() {
+        println!("  Points dropped for flags ({}): {}", options.drop_flags, dropped_by_flags);
+    }
+    if !options.region.is_empty() {
+        println!("  Region of interest: {}", options.region);
+        println!("  Points outside the region: {}", outside_region);
+    }
+    if let Some(transform) = &source.transform {
+        println!("  Sensor transforms: {}", transform);
     }
     println!("=======================================================\n");
 
-    println!("Press Enter to exit...");
-    let mut _dummy = String::new();
-    io::stdin().read_line(&mut _dummy)?;
-
     Ok(())
 }
+
+/// Writer of the selected output format for `output_path`
+fn new_writer(
+    options: &cli::CliOptions,
+    output_path: &str,
+    source: &export::SourceInfo,
+    sensors: &BTreeMap<SocketAddr, pcap_reader::SensorStats>,
+) -> Box<dyn PointWriter> {
+    let format = options.output_format();
+    match format {
+        export::OutputFormat::Xlsx => {
+            let excel_options = excel_exporter::ExcelOptions {
+                columns: options.columns,
+                split: options.split,
+                per_sensor_files: options.per_sensor_files,
+                ..Default::default()
+            };
+            Box::new(excel_exporter::ExcelWriter::new(output_path, &excel_options, source, sensors))
+        }
+        export::OutputFormat::Csv | export::OutputFormat::Tsv => {
+            let csv_options = csv_exporter::CsvOptions {
+                columns: options.columns,
+                layout: options.layout,
+                per_sensor_files: options.per_sensor_files,
+                tab_separated: format == export::OutputFormat::Tsv,
+            };
+            Box::new(csv_exporter::CsvWriter::new(output_path, &csv_options, sensors))
+        }
+        export::OutputFormat::Pcd => {
+            let pcd_options = pcd_exporter::PcdOptions {
+                encoding: options.pcd_encoding,
+                layout: options.layout,
+                per_sensor_files: options.per_sensor_files,
+                polar: options.columns.polar,
+            };
+            Box::new(pcd_exporter::PcdWriter::new(output_path, &pcd_options, sensors.keys().copied()))
+        }
+        export::OutputFormat::Ply => {
+            let ply_options = ply_exporter::PlyOptions {
+                encoding: options.ply_encoding,
+                layout: options.layout,
+                per_sensor_files: options.per_sensor_files,
+                polar: options.columns.polar,
+            };
+            Box::new(ply_exporter::PlyWriter::new(output_path, &ply_options, sensors))
+        }
+        export::OutputFormat::Las => {
+            let las_options = las_exporter::LasOptions {
+                layout: options.layout,
+                per_sensor_files: options.per_sensor_files,
+                polar: options.columns.polar,
+            };
+            Box::new(las_exporter::LasWriter::new(output_path, &las_options, sensors.keys().copied()))
+        }
+        export::OutputFormat::Parquet => {
+            let parquet_options = parquet_exporter::ParquetOptions {
+                layout: options.layout,
+                per_sensor_files: options.per_sensor_files,
+                polar: options.columns.polar,
+            };
+            Box::new(parquet_exporter::ParquetWriter::new(
+                output_path,
+                &parquet_options,
+                source,
+                sensors.keys().copied(),
+            ))
+        }
+        export::OutputFormat::Npy | export::OutputFormat::Npz => {
+            let npy_options = npy_exporter::NpyOptions {
+                bundle: format == export::OutputFormat::Npz,
+                layout: options.layout,
+                per_sensor_files: options.per_sensor_files,
+                polar: options.columns.polar,
+            };
+            Box::new(npy_exporter::NpyWriter::new(output_path, &npy_options, sensors))
+        }
+    }
+}
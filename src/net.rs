@@ -0,0 +1,306 @@
+//! Link, network and transport header decoding down to the UDP payload
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+/// Link types (LINKTYPE_* values as stored in pcap/pcapng headers)
+pub const LINKTYPE_NULL: u32 = 0;
+pub const LINKTYPE_ETHERNET: u32 = 1;
+pub const LINKTYPE_RAW_DLT: u32 = 12; // some writers store DLT_RAW instead of LINKTYPE_RAW
+pub const LINKTYPE_RAW: u32 = 101;
+pub const LINKTYPE_LOOP: u32 = 108;
+pub const LINKTYPE_LINUX_SLL: u32 = 113;
+pub const LINKTYPE_IPV4: u32 = 228;
+pub const LINKTYPE_IPV6: u32 = 229;
+pub const LINKTYPE_LINUX_SLL2: u32 = 276;
+
+/// EtherType values
+const ETHERTYPE_IPV4: u16 = 0x0800;
+const ETHERTYPE_IPV6: u16 = 0x86DD;
+const ETHERTYPE_VLAN: u16 = 0x8100;
+const ETHERTYPE_QINQ: u16 = 0x88A8;
+const ETHERTYPE_QINQ_OLD: u16 = 0x9100;
+
+/// IP protocol numbers
+const IPPROTO_HOPOPTS: u8 = 0;
+const IPPROTO_UDP: u8 = 17;
+const IPPROTO_ROUTING: u8 = 43;
+const IPPROTO_FRAGMENT: u8 = 44;
+const IPPROTO_AH: u8 = 51;
+const IPPROTO_DSTOPTS: u8 = 60;
+
+/// A decoded UDP datagram
+#[derive(Debug, Clone, PartialEq)]
+pub struct UdpDatagram<'a> {
+    pub src_ip: IpAddr,
+    pub dst_ip: IpAddr,
+    pub src_port: u16,
+    pub dst_port: u16,
+    pub payload: &'a [u8],
+}
+
+/// Walk the link-layer and IP headers of a captured frame and return its UDP datagram.
+/// Returns None for non-UDP traffic, unsupported link types, truncated frames
+/// and IP fragments (the payload cannot be reassembled from a single frame).
+pub fn decode_udp(link_type: u32, frame: &[u8]) -> Option<UdpDatagram<'_>> {
+    match link_type {
+        LINKTYPE_ETHERNET => decode_ethernet(frame),
+        LINKTYPE_LINUX_SLL => {
+            // Linux cooked v1: 16-byte header, protocol in the last two bytes
+            let protocol = be_u16(frame.get(14..16)?);
+            decode_ethertype(protocol, frame.get(16..)?)
+        }
+        LINKTYPE_LINUX_SLL2 => {
+            // Linux cooked v2: 20-byte header, protocol first
+            let protocol = be_u16(frame.get(0..2)?);
+            decode_ethertype(protocol, frame.get(20..)?)
+        }
+        LINKTYPE_RAW | LINKTYPE_RAW_DLT => decode_ip(frame),
+        LINKTYPE_IPV4 => decode_ipv4(frame),
+        LINKTYPE_IPV6 => decode_ipv6(frame),
+        LINKTYPE_NULL | LINKTYPE_LOOP => {
+            // BSD loopback: 4-byte address family in unknown byte order; sniff the IP version
+            decode_ip(frame.get(4..)?)
+        }
+        _ => None,
+    }
+}
+
+/// Ethernet II with any number of 802.1Q / 802.1ad tags
+fn decode_ethernet(frame: &[u8]) -> Option<UdpDatagram<'_>> {
+    let mut offset = 12;
+    let mut ethertype = be_u16(frame.get(offset..offset + 2)?);
+    offset += 2;
+
+    while matches!(ethertype, ETHERTYPE_VLAN | ETHERTYPE_QINQ | ETHERTYPE_QINQ_OLD) {
+        // Tag control information (2) followed by the inner EtherType (2)
+        ethertype = be_u16(frame.get(offset + 2..offset + 4)?);
+        offset += 4;
+    }
+
+    decode_ethertype(ethertype, frame.get(offset..)?)
+}
+
+fn decode_ethertype(ethertype: u16, data: &[u8]) -> Option<UdpDatagram<'_>> {
+    match ethertype {
+        ETHERTYPE_IPV4 => decode_ipv4(data),
+        ETHERTYPE_IPV6 => decode_ipv6(data),
+        _ => None,
+    }
+}
+
+/// IP packet of either version, chosen by the version nibble
+fn decode_ip(data: &[u8]) -> Option<UdpDatagram<'_>> {
+    match data.first()? >> 4 {
+        4 => decode_ipv4(data),
+        6 => decode_ipv6(data),
+        _ => None,
+    }
+}
+
+fn decode_ipv4(data: &[u8]) -> Option<UdpDatagram<'_>> {
+    let version = data.first()? >> 4;
+    let header_len = ((data[0] & 0x0f) as usize) * 4;
+    if version != 4 || header_len < 20 || data.len() < header_len {
+        return None;
+    }
+
+    // Drop link-layer padding after the IP packet
+    let total_len = be_u16(&data[2..4]) as usize;
+    let data = if total_len >= header_len && total_len <= data.len() {
+        &data[..total_len]
+    } else {
+        data
+    };
+
+    // More-fragments flag or non-zero fragment offset
+    let fragment = be_u16(&data[6..8]);
+    if fragment & 0x3fff != 0 {
+        return None;
+    }
+
+    if data[9] != IPPROTO_UDP {
+        return None;
+    }
+
+    let src_ip = IpAddr::V4(Ipv4Addr::new(data[12], data[13], data[14], data[15]));
+    let dst_ip = IpAddr::V4(Ipv4Addr::new(data[16], data[17], data[18], data[19]));
+    decode_udp_header(src_ip, dst_ip, &data[header_len..])
+}
+
+fn decode_ipv6(data: &[u8]) -> Option<UdpDatagram<'_>> {
+    if data.len() < 40 || data[0] >> 4 != 6 {
+        return None;
+    }
+
+    // Drop link-layer padding after the IP packet
+    let payload_len = be_u16(&data[4..6]) as usize;
+    let end = if payload_len > 0 && 40 + payload_len <= data.len() {
+        40 + payload_len
+    } else {
+        data.len()
+    };
+
+    let src: [u8; 16] = data[8..24].try_into().ok()?;
+    let dst: [u8; 16] = data[24..40].try_into().ok()?;
+    let src_ip = IpAddr::V6(Ipv6Addr::from(src));
+    let dst_ip = IpAddr::V6(Ipv6Addr::from(dst));
+
+    // Follow the extension header chain up to UDP
+    let mut next_header = data[6];
+    let mut offset = 40;
+    loop {
+        match next_header {
+            IPPROTO_UDP => return decode_udp_header(src_ip, dst_ip, data.get(offset..end)?),
+            IPPROTO_HOPOPTS | IPPROTO_ROUTING | IPPROTO_DSTOPTS => {
+                let ext = data.get(offset..offset + 2)?;
+                next_header = ext[0];
+                offset += (ext[1] as usize + 1) * 8;
+            }
+            IPPROTO_AH => {
+                let ext = data.get(offset..offset + 2)?;
+                next_header = ext[0];
+                offset += (ext[1] as usize + 2) * 4;
+            }
+            IPPROTO_FRAGMENT => {
+                let ext = data.get(offset..offset + 8)?;
+                // Fragment offset or more-fragments flag set
+                if be_u16(&ext[2..4]) & 0xfff9 != 0 {
+                    return None;
+                }
+                next_header = ext[0];
+                offset += 8;
+            }
+            _ => return None,
+        }
+    }
+}
+
+fn decode_udp_header(src_ip: IpAddr, dst_ip: IpAddr, data: &[u8]) -> Option<UdpDatagram<'_>> {
+    let header = data.get(0..8)?;
+    let src_port = be_u16(&header[0..2]);
+    let dst_port = be_u16(&header[2..4]);
+
+    // Trust the UDP length when it is consistent (0 is used for IPv6 jumbograms)
+    let udp_len = be_u16(&header[4..6]) as usize;
+    let payload = if udp_len >= 8 && udp_len <= data.len() {
+        &data[8..udp_len]
+    } else {
+        &data[8..]
+    };
+
+    Some(UdpDatagram {
+        src_ip,
+        dst_ip,
+        src_port,
+        dst_port,
+        payload,
+    })
+}
+
+fn be_u16(b: &[u8]) -> u16 {
+    u16::from_be_bytes([b[0], b[1]])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn udp(payload: &[u8]) -> Vec<u8> {
+        let mut out = Vec::new();
+        out.extend_from_slice(&8808u16.to_be_bytes());
+        out.extend_from_slice(&9000u16.to_be_bytes());
+        out.extend_from_slice(&((payload.len() + 8) as u16).to_be_bytes());
+        out.extend_from_slice(&[0, 0]);
+        out.extend_from_slice(payload);
+        out
+    }
+
+    fn ipv4(options: &[u8], udp: &[u8]) -> Vec<u8> {
+        let header_len = 20 + options.len();
+        let mut out = vec![0x40 | (header_len / 4) as u8, 0];
+        out.extend_from_slice(&((header_len + udp.len()) as u16).to_be_bytes());
+        out.extend_from_slice(&[0, 0, 0x40, 0, 64, IPPROTO_UDP, 0, 0]);
+        out.extend_from_slice(&[192, 168, 1, 201]);
+        out.extend_from_slice(&[192, 168, 1, 10]);
+        out.extend_from_slice(options);
+        out.extend_from_slice(udp);
+        out
+    }
+
+    fn ipv6(udp: &[u8]) -> Vec<u8> {
+        // Hop-by-hop options header in front of UDP
+        let mut out = vec![0x60, 0, 0, 0];
+        out.extend_from_slice(&((udp.len() + 8) as u16).to_be_bytes());
+        out.extend_from_slice(&[IPPROTO_HOPOPTS, 64]);
+        out.extend_from_slice(&Ipv6Addr::LOCALHOST.octets());
+        out.extend_from_slice(&Ipv6Addr::LOCALHOST.octets());
+        out.extend_from_slice(&[IPPROTO_UDP, 0, 1, 4, 0, 0, 0, 0]);
+        out.extend_from_slice(udp);
+        out
+    }
+
+    fn ethernet(tags: usize, ethertype: u16, ip: &[u8]) -> Vec<u8> {
+        let mut out = vec![0xff; 12];
+        for _ in 0..tags {
+            out.extend_from_slice(&ETHERTYPE_VLAN.to_be_bytes());
+            out.extend_from_slice(&[0x00, 0x05]);
+        }
+        out.extend_from_slice(&ethertype.to_be_bytes());
+        out.extend_from_slice(ip);
+        out.extend_from_slice(&[0u8; 6]); // trailing padding
+        out
+    }
+
+    #[test]
+    fn test_ethernet_vlan_ipv4_options() {
+        let frame = ethernet(2, ETHERTYPE_IPV4, &ipv4(&[1, 1, 1, 0], &udp(b"STDV")));
+        let datagram = decode_udp(LINKTYPE_ETHERNET, &frame).unwrap();
+
+        assert_eq!(datagram.payload, b"STDV");
+        assert_eq!(datagram.src_ip, IpAddr::V4(Ipv4Addr::new(192, 168, 1, 201)));
+        assert_eq!(datagram.src_port, 8808);
+        assert_eq!(datagram.dst_port, 9000);
+    }
+
+    #[test]
+    fn test_ethernet_ipv6_extension_header() {
+        let frame = ethernet(0, ETHERTYPE_IPV6, &ipv6(&udp(b"STDV")));
+        let datagram = decode_udp(LINKTYPE_ETHERNET, &frame).unwrap();
+
+        assert_eq!(datagram.payload, b"STDV");
+        assert_eq!(datagram.dst_ip, IpAddr::V6(Ipv6Addr::LOCALHOST));
+    }
+
+    #[test]
+    fn test_cooked_and_raw_link_types() {
+        let ip = ipv4(&[], &udp(b"payload"));
+
+        let mut sll = vec![0u8; 14];
+        sll.extend_from_slice(&ETHERTYPE_IPV4.to_be_bytes());
+        sll.extend_from_slice(&ip);
+        assert_eq!(decode_udp(LINKTYPE_LINUX_SLL, &sll).unwrap().payload, b"payload");
+
+        let mut sll2 = ETHERTYPE_IPV4.to_be_bytes().to_vec();
+        sll2.extend_from_slice(&[0u8; 18]);
+        sll2.extend_from_slice(&ip);
+        assert_eq!(decode_udp(LINKTYPE_LINUX_SLL2, &sll2).unwrap().payload, b"payload");
+
+        assert_eq!(decode_udp(LINKTYPE_RAW, &ip).unwrap().payload, b"payload");
+        assert_eq!(decode_udp(LINKTYPE_IPV4, &ip).unwrap().payload, b"payload");
+        assert_eq!(decode_udp(LINKTYPE_RAW, &ipv6(&udp(b"v6"))).unwrap().payload, b"v6");
+    }
+
+    #[test]
+    fn test_rejects_non_udp_and_fragments() {
+        let mut tcp = ipv4(&[], &udp(b"x"));
+        tcp[9] = 6;
+        assert!(decode_udp(LINKTYPE_RAW, &tcp).is_none());
+
+        let mut fragment = ipv4(&[], &udp(b"x"));
+        fragment[6] = 0x20; // more fragments
+        assert!(decode_udp(LINKTYPE_RAW, &fragment).is_none());
+
+        assert!(decode_udp(LINKTYPE_ETHERNET, &[0u8; 10]).is_none());
+        assert!(decode_udp(147, &ipv4(&[], &udp(b"x"))).is_none());
+    }
+}
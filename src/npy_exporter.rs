@@ -0,0 +1,297 @@
+//! NumPy export - structured .npy arrays, or one .npz bundle of them, for `np.load`.
+//! Rows are streamed; the array length in the .npy header is patched in place once
+//! the array is complete. An .npz bundle is assembled from temporary .npy files at the end,
+//! since its arrays are written interleaved.
+
+use crate::cepton::{ChannelKey, Point};
+use crate::export::{ExportSummary, Layout, MAX_SHEET_NAME_LEN, OpenFiles, OutputFiles, PointWriter};
+use crate::pcap_reader::SensorStats;
+use anyhow::{Context, Result};
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Seek, SeekFrom, Write};
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use zip::write::{SimpleFileOptions, ZipWriter};
+
+/// NumPy writer options
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NpyOptions {
+    pub bundle: bool, // one .npz holding every array instead of .npy files
+    pub layout: Layout,
+    pub per_sensor_files: bool,
+    pub polar: bool, // add the range, azimuth and elevation fields
+}
+
+/// Fields of every row: (name, dtype), in `Point` order
+const FIELDS: [(&str, &str); 13] = [
+    ("x", "<f8"),
+    ("y", "<f8"),
+    ("z", "<f8"),
+    ("reflectivity", "|u1"),
+    ("flags", "|u1"),
+    ("timestamp_us", "<u8"),
+    ("capture_time_ns", "<u8"),
+    ("channel", "|u1"),
+    ("sequence_id", "<u4"),
+    ("packet_index", "<u8"),
+    ("point_index", "<u2"),
+    ("frame", "<u8"),
+    ("sensor", "|S31"), // label, only in files that mix sensors
+];
+
+/// Fields added by `NpyOptions::polar`: meters, degrees, degrees
+const POLAR_FIELDS: [(&str, &str); 3] = [("range", "<f8"), ("azimuth", "<f8"), ("elevation", "<f8")];
+
+/// Extra fields of Debug mode rows
+const DEBUG_FIELDS: [(&str, &str); 4] =
+    [("has_debug", "|b1"), ("distance", "<u4"), ("intensity", "<u2"), ("power_level", "|u1")];
+
+/// Digits reserved for the array length so the header can be rewritten in place
+const COUNT_DIGITS: usize = 20;
+
+/// Base name of the temporary arrays of a bundle; stripped from the array names
+const BUNDLE_STEM: &str = "points";
+
+/// Streams points into one structured array per channel, or into one merged array
+pub struct NpyWriter {
+    output_path: String,
+    bundle_dir: Option<PathBuf>, // temporary .npy files of an .npz bundle
+    outputs: OpenFiles<NpyFile>,
+    polar: bool,
+}
+
+/// One open array file
+struct NpyFile {
+    writer: BufWriter<File>,
+    rows: u64,
+    debug: bool,        // has the Debug mode fields
+    sensor_field: bool, // mixes sensors, so rows carry the sensor label
+}
+
+impl NpyWriter {
+    /// Start an export to `output_path`; `sensors` are the sensors whose points will be written
+    pub fn new(output_path: &str, options: &NpyOptions, sensors: &BTreeMap<SocketAddr, SensorStats>) -> Self {
+        // A bundle's arrays are collected next to it and zipped by `finish`
+        let bundle_dir = options.bundle.then(|| PathBuf::from(format!("{}.parts", output_path)));
+        let files_path = match &bundle_dir {
+            Some(dir) => dir.join(format!("{}.npy", BUNDLE_STEM)).to_string_lossy().into_owned(),
+            None => output_path.to_string(),
+        };
+
+        let files = OutputFiles::for_sensors(&files_path, options.layout, options.per_sensor_files, sensors);
+        NpyWriter {
+            output_path: output_path.to_string(),
+            bundle_dir,
+            outputs: OpenFiles::new(files),
+            polar: options.polar,
+        }
+    }
+}
+
+/// Create a file with a placeholder header; `debug` adds the Debug mode fields
+fn open(path: &str, polar: bool, sensor_field: bool, debug: bool) -> Result<NpyFile> {
+    if let Some(dir) = Path::new(path).parent() {
+        fs::create_dir_all(dir).with_context(|| format!("Failed to create directory: {}", dir.display()))?;
+    }
+    let file = File::create(path).with_context(|| format!("Failed to create file: {}", path))?;
+    let mut writer = BufWriter::new(file);
+    writer.write_all(&header(0, polar, debug, sensor_field))?;
+
+    Ok(NpyFile { writer, rows: 0, debug, sensor_field })
+}
+
+impl PointWriter for NpyWriter {
+    fn write_point(&mut self, key: ChannelKey, point: &Point) -> Result<()> {
+        let files = self.outputs.files();
+        let sensor_field = files.mixes_sensors();
+        let label = sensor_field.then(|| files.label(key.sensor));
+        let debug = files.has_debug(key);
+
+        let polar = self.polar;
+        let file = self.outputs.get_or_open(key, |path| open(path, polar, sensor_field, debug))?;
+        let output = &mut file.output;
+        output.rows += 1;
+
+        let mut row = Vec::with_capacity(128);
+        row.extend_from_slice(&point.x.to_le_bytes());
+        row.extend_from_slice(&point.y.to_le_bytes());
+        row.extend_from_slice(&point.z.to_le_bytes());
+        row.push(point.reflectivity);
+        row.push(point.flags);
+        row.extend_from_slice(&point.timestamp_us.to_le_bytes());
+        row.extend_from_slice(&point.capture_time_ns.to_le_bytes());
+        row.push(point.channel);
+        row.extend_from_slice(&point.sequence_id.to_le_bytes());
+        row.extend_from_slice(&point.packet_index.to_le_bytes());
+        row.extend_from_slice(&point.point_index.to_le_bytes());
+        row.extend_from_slice(&point.frame.to_le_bytes());
+        if let Some(label) = label {
+            // Fixed width, NUL padded; labels always fit a sheet name
+            let mut field = [0; MAX_SHEET_NAME_LEN];
+            let len = label.len().min(MAX_SHEET_NAME_LEN);
+            field[..len].copy_from_slice(&label.as_bytes()[..len]);
+            row.extend_from_slice(&field);
+        }
+        if polar {
+            row.extend_from_slice(&point.range().to_le_bytes());
+            row.extend_from_slice(&point.azimuth().to_le_bytes());
+            row.extend_from_slice(&point.elevation().to_le_bytes());
+        }
+        if output.debug {
+            // Points without debug data in a debug file get zeros and has_debug False
+            row.push(point.distance.is_some() as u8);
+            row.extend_from_slice(&point.distance.unwrap_or_default().to_le_bytes());
+            row.extend_from_slice(&point.intensity.unwrap_or_default().to_le_bytes());
+            row.push(point.power_level.unwrap_or_default());
+        }
+
+        output.writer
+            .write_all(&row)
+            .with_context(|| format!("Failed to write file: {}", file.path))
+    }
+
+    fn finish(self: Box<Self>) -> Result<ExportSummary> {
+        let polar = self.polar;
+        let mut arrays = Vec::new();
+        for file in self.outputs.into_sorted() {
+            let context = || format!("Failed to write file: {}", file.path);
+            let output = file.output;
+
+            // Now that the number of rows is known, fill it into the header
+            let mut handle = output.writer.into_inner().map_err(|e| e.into_error()).with_context(context)?;
+            handle.seek(SeekFrom::Start(0)).with_context(context)?;
+            handle
+                .write_all(&header(output.rows, polar, output.debug, output.sensor_field))
+                .with_context(context)?;
+
+            arrays.push(file.path);
+        }
+
+        let mut summary = ExportSummary::default();
+        match self.bundle_dir {
+            Some(dir) => {
+                write_bundle(&self.output_path, &arrays)
+                    .with_context(|| format!("Failed to write file: {}", self.output_path))?;
+                if dir.exists() {
+                    fs::remove_dir_all(&dir)
+                        .with_context(|| format!("Failed to remove directory: {}", dir.display()))?;
+                }
+                summary.files.push(self.output_path);
+            }
+            None => summary.files = arrays,
+        }
+
+        Ok(summary)
+    }
+}
+
+/// Zip the arrays into an .npz; each is named after its file, without the bundle stem
+fn write_bundle(path: &str, arrays: &[String]) -> Result<()> {
+    let mut zip = ZipWriter::new(File::create(path)?);
+
+    for array in arrays {
+        let stem = Path::new(array).file_stem().and_then(|s| s.to_str()).unwrap_or(BUNDLE_STEM);
+        let name = stem.strip_prefix(&format!("{}_", BUNDLE_STEM)).unwrap_or(stem);
+
+        let mut file = File::open(array)?;
+        let size = file.metadata()?.len();
+        let options = SimpleFileOptions::default()
+            .compression_method(zip::CompressionMethod::Deflated)
+            .large_file(size >= u32::MAX as u64);
+        zip.start_file(format!("{}.npy", name), options)?;
+        io::copy(&mut file, &mut zip)?;
+    }
+
+    zip.finish()?;
+    Ok(())
+}
+
+/// NPY 1.0 header; the length is space padded so every header has the same size
+fn header(rows: u64, polar: bool, debug: bool, sensor_field: bool) -> Vec<u8> {
+    let polar: &[(&str, &str)] = if polar { &POLAR_FIELDS } else { &[] };
+    let extra: &[(&str, &str)] = if debug { &DEBUG_FIELDS } else { &[] };
+    let descr: Vec<String> = FIELDS
+        .iter()
+        .filter(|(name, _)| sensor_field || *name != "sensor")
+        .chain(polar)
+        .chain(extra)
+        .map(|(name, dtype)| format!("('{}', '{}')", name, dtype))
+        .collect();
+
+    let mut dict = format!(
+        "{{'descr': [{}], 'fortran_order': False, 'shape': ({:>width$},), }}",
+        descr.join(", "),
+        rows,
+        width = COUNT_DIGITS,
+    );
+
+    // magic + version + length + dict + newline is a multiple of 64 bytes
+    let unpadded = 6 + 2 + 2 + dict.len() + 1;
+    dict.push_str(&" ".repeat(unpadded.next_multiple_of(64) - unpadded));
+    dict.push('\n');
+
+    let mut header = b"\x93NUMPY\x01\x00".to_vec();
+    header.extend_from_slice(&(dict.len() as u16).to_le_bytes());
+    header.extend_from_slice(dict.as_bytes());
+    header
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    #[test]
+    fn test_npy_header() {
+        let header = header(42, false, true, false);
+        assert_eq!(header.len() % 64, 0);
+        assert_eq!(&header[..8], b"\x93NUMPY\x01\x00");
+
+        let dict = std::str::from_utf8(&header[10..]).unwrap();
+        assert!(dict.starts_with("{'descr': [('x', '<f8'), ('y', '<f8'), ('z', '<f8'), ('reflectivity', '|u1'),"));
+        assert!(dict.contains("('power_level', '|u1')], 'fortran_order': False, 'shape': ("));
+        assert!(dict.contains(" 42,), }"));
+        assert!(!dict.contains("sensor"));
+        assert!(dict.ends_with(" \n"));
+
+        let polar = super::header(1, true, true, true);
+        let dict = std::str::from_utf8(&polar[10..]).unwrap();
+        assert!(dict.contains("('sensor', '|S31'), ('range', '<f8'), ('azimuth', '<f8'), ('elevation', '<f8'), ('has_debug', '|b1'), ('distance',"));
+    }
+
+    #[test]
+    fn test_npz_export() {
+        let a: SocketAddr = "192.168.1.201:8808".parse().unwrap();
+        let b: SocketAddr = "192.168.1.202:8808".parse().unwrap();
+        let point = Point { x: 1.5, reflectivity: 40, channel: 3, sequence_id: 7, ..Default::default() };
+
+        let options = NpyOptions { bundle: true, ..Default::default() };
+        let sensors = BTreeMap::from([(a, SensorStats::default()), (b, SensorStats::default())]);
+        let mut writer = Box::new(NpyWriter::new("test_bundle.npz", &options, &sensors));
+        writer.write_point(ChannelKey { sensor: b, channel: 3 }, &point).unwrap();
+        writer.write_point(ChannelKey { sensor: a, channel: 3 }, &point).unwrap();
+        writer.write_point(ChannelKey { sensor: a, channel: 3 }, &point).unwrap();
+        let summary = writer.finish().unwrap();
+
+        assert_eq!(summary.files, vec!["test_bundle.npz"]);
+        assert!(!Path::new("test_bundle.npz.parts").exists());
+
+        let mut zip = zip::ZipArchive::new(File::open("test_bundle.npz").unwrap()).unwrap();
+        let names: Vec<_> = zip.file_names().map(String::from).collect();
+        let mut array = Vec::new();
+        zip.by_name("192.168.1.201_Channel_3.npy").unwrap().read_to_end(&mut array).unwrap();
+        let _ = std::fs::remove_file("test_bundle.npz");
+
+        assert_eq!(names.len(), 2);
+        assert!(names.contains(&"192.168.1.202_Channel_3.npy".to_string()));
+
+        let header_len = header(2, false, false, false).len();
+        assert_eq!(array[..header_len], header(2, false, false, false)[..]);
+        let row_size = 3 * 8 + 2 + 2 * 8 + 1 + 4 + 8 + 2 + 8;
+        assert_eq!(array.len(), header_len + 2 * row_size);
+        assert_eq!(&array[header_len..header_len + 8], &1.5f64.to_le_bytes());
+        assert_eq!(array[header_len + 24], 40);
+        assert_eq!(array[header_len + 42], 3); // channel
+    }
+}
@@ -0,0 +1,347 @@
+//! Apache Parquet export - typed columns for analytics tools (pandas, Polars, DuckDB, Spark).
+//! Points are buffered per file and written as a row group every `ROW_GROUP_ROWS` points,
+//! so memory stays bounded however long the capture is.
+
+use crate::cepton::{ChannelKey, Point};
+use crate::export::{ExportSummary, Layout, OpenFiles, OutputFiles, PointWriter, SourceInfo};
+use anyhow::{Context, Result};
+use parquet::basic::Compression;
+use parquet::data_type::{ByteArray, ByteArrayType, DataType, FloatType, Int32Type, Int64Type};
+use parquet::file::properties::WriterProperties;
+use parquet::file::writer::{SerializedFileWriter, SerializedRowGroupWriter};
+use parquet::format::KeyValue;
+use parquet::schema::parser::parse_message_type;
+use std::fs::File;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+/// Parquet writer options
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ParquetOptions {
+    pub layout: Layout,
+    pub per_sensor_files: bool,
+    pub polar: bool, // add the range, azimuth and elevation columns
+}
+
+/// Rows per row group; large enough for efficient scans, small enough to buffer per file
+const ROW_GROUP_ROWS: usize = 131_072;
+
+/// Every file has the same columns, whatever the layout and parse mode: these, the polar
+/// columns if enabled, then the Debug fields.
+/// Unsigned values are stored in the signed physical types, as the format specifies.
+const FIELDS: &str = "
+    required binary sensor (UTF8);
+    required int32 channel (INTEGER(8,false));
+    required float x;
+    required float y;
+    required float z;
+    required int32 reflectivity (INTEGER(8,false));
+    required int32 flags (INTEGER(8,false));
+    required int64 timestamp_us (INTEGER(64,false));
+    required int64 capture_time_ns (INTEGER(64,false));
+    required int32 sequence_id (INTEGER(32,false));
+    required int64 packet_index (INTEGER(64,false));
+    required int32 point_index (INTEGER(16,false));
+    required int64 frame (INTEGER(64,false));
+";
+
+/// Columns added by `ParquetOptions::polar`: meters, degrees, degrees
+const POLAR_FIELDS: &str = "
+    required float range;
+    required float azimuth;
+    required float elevation;
+";
+
+const DEBUG_FIELDS: &str = "
+    optional int32 distance (INTEGER(32,false));
+    optional int32 intensity (INTEGER(16,false));
+    optional int32 power_level (INTEGER(8,false));
+";
+
+/// Streams points into one Parquet file per channel, or into one merged file
+pub struct ParquetWriter {
+    outputs: OpenFiles<ParquetFile>,
+    properties: Arc<WriterProperties>,
+    polar: bool,
+}
+
+/// One open output file and the rows of its next row group
+struct ParquetFile {
+    writer: SerializedFileWriter<File>,
+    rows: Rows,
+}
+
+/// Column buffers of a row group
+#[derive(Default)]
+struct Rows {
+    sensor: Vec<ByteArray>,
+    channel: Vec<i32>,
+    x: Vec<f32>,
+    y: Vec<f32>,
+    z: Vec<f32>,
+    reflectivity: Vec<i32>,
+    flags: Vec<i32>,
+    timestamp_us: Vec<i64>,
+    capture_time_ns: Vec<i64>,
+    sequence_id: Vec<i32>,
+    packet_index: Vec<i64>,
+    point_index: Vec<i32>,
+    frame: Vec<i64>,
+    // Polar fields, left empty unless enabled
+    range: Vec<f32>,
+    azimuth: Vec<f32>,
+    elevation: Vec<f32>,
+    // Debug fields: only points that have them get a value, `debug` holds the definition levels
+    distance: Vec<i32>,
+    intensity: Vec<i32>,
+    power_level: Vec<i32>,
+    debug: Vec<i16>,
+}
+
+impl ParquetWriter {
+    /// Start an export to `output_path`; `sensors` are the sensors whose points will be written.
+    /// `source` is stored in the key-value metadata of every file.
+    pub fn new(
+        output_path: &str,
+        options: &ParquetOptions,
+        source: &SourceInfo,
+        sensors: impl Iterator<Item = SocketAddr>,
+    ) -> Self {
+        let files = OutputFiles::new(output_path, options.layout, options.per_sensor_files, sensors);
+
+        let mut metadata = vec![
+            KeyValue::new("source_file".to_string(), source.file.clone()),
+            KeyValue::new("parse_mode".to_string(), source.mode.clone()),
+        ];
+        if let Some(window) = &source.window {
+            metadata.push(KeyValue::new("time_window".to_string(), window.clone()));
+        }
+        if let Some(region) = &source.region {
+            metadata.push(KeyValue::new("region".to_string(), region.clone()));
+        }
+        if let Some(transform) = &source.transform {
+            metadata.push(KeyValue::new("sensor_transform".to_string(), transform.clone()));
+        }
+        let properties = WriterProperties::builder()
+            .set_compression(Compression::SNAPPY)
+            .set_max_row_group_size(ROW_GROUP_ROWS)
+            .set_created_by(concat!(env!("CARGO_PKG_NAME"), " ", env!("CARGO_PKG_VERSION")).to_string())
+            .set_key_value_metadata(Some(metadata))
+            .build();
+
+        ParquetWriter {
+            outputs: OpenFiles::new(files),
+            properties: Arc::new(properties),
+            polar: options.polar,
+        }
+    }
+}
+
+/// Message type of the file schema
+fn schema(polar: bool) -> String {
+    let polar = if polar { POLAR_FIELDS } else { "" };
+    format!("message cepton_point {{{}{}{}}}", FIELDS, polar, DEBUG_FIELDS)
+}
+
+/// Create a file and write its schema
+fn open(path: &str, properties: &Arc<WriterProperties>, polar: bool) -> Result<ParquetFile> {
+    let file = File::create(path).with_context(|| format!("Failed to create file: {}", path))?;
+    let schema = Arc::new(parse_message_type(&schema(polar))?);
+    let writer = SerializedFileWriter::new(file, schema, properties.clone())?;
+
+    Ok(ParquetFile { writer, rows: Rows::default() })
+}
+
+impl PointWriter for ParquetWriter {
+    fn write_point(&mut self, key: ChannelKey, point: &Point) -> Result<()> {
+        let (properties, polar) = (&self.properties, self.polar);
+        let file = self.outputs.get_or_open(key, |path| open(path, properties, polar))?;
+        let output = &mut file.output;
+
+        output.rows.push(key.sensor, point, polar);
+        if output.rows.len() >= ROW_GROUP_ROWS {
+            output.rows
+                .write(&mut output.writer, polar)
+                .with_context(|| format!("Failed to write file: {}", file.path))?;
+        }
+
+        Ok(())
+    }
+
+    fn finish(self: Box<Self>) -> Result<ExportSummary> {
+        let polar = self.polar;
+        let mut summary = ExportSummary::default();
+        for file in self.outputs.into_sorted() {
+            let context = || format!("Failed to write file: {}", file.path);
+            let mut output = file.output;
+
+            if output.rows.len() > 0 {
+                output.rows.write(&mut output.writer, polar).with_context(context)?;
+            }
+            output.writer.close().with_context(context)?;
+
+            summary.files.push(file.path);
+        }
+
+        Ok(summary)
+    }
+}
+
+impl Rows {
+    fn len(&self) -> usize {
+        self.x.len()
+    }
+
+    fn push(&mut self, sensor: SocketAddr, point: &Point, polar: bool) {
+        self.sensor.push(ByteArray::from(sensor.to_string().as_str()));
+        self.channel.push(point.channel as i32);
+        self.x.push(point.x as f32);
+        self.y.push(point.y as f32);
+        self.z.push(point.z as f32);
+        self.reflectivity.push(point.reflectivity as i32);
+        self.flags.push(point.flags as i32);
+        self.timestamp_us.push(point.timestamp_us as i64);
+        self.capture_time_ns.push(point.capture_time_ns as i64);
+        self.sequence_id.push(point.sequence_id as i32);
+        self.packet_index.push(point.packet_index as i64);
+        self.point_index.push(point.point_index as i32);
+        self.frame.push(point.frame as i64);
+        if polar {
+            self.range.push(point.range() as f32);
+            self.azimuth.push(point.azimuth() as f32);
+            self.elevation.push(point.elevation() as f32);
+        }
+
+        match (point.distance, point.intensity, point.power_level) {
+            (Some(distance), Some(intensity), Some(power_level)) => {
+                self.distance.push(distance as i32);
+                self.intensity.push(intensity as i32);
+                self.power_level.push(power_level as i32);
+                self.debug.push(1);
+            }
+            _ => self.debug.push(0),
+        }
+    }
+
+    /// Write the buffered rows as one row group, in schema order, and clear them
+    fn write(&mut self, writer: &mut SerializedFileWriter<File>, polar: bool) -> Result<()> {
+        let mut group = writer.next_row_group()?;
+        write_column::<ByteArrayType>(&mut group, &self.sensor, None)?;
+        write_column::<Int32Type>(&mut group, &self.channel, None)?;
+        write_column::<FloatType>(&mut group, &self.x, None)?;
+        write_column::<FloatType>(&mut group, &self.y, None)?;
+        write_column::<FloatType>(&mut group, &self.z, None)?;
+        write_column::<Int32Type>(&mut group, &self.reflectivity, None)?;
+        write_column::<Int32Type>(&mut group, &self.flags, None)?;
+        write_column::<Int64Type>(&mut group, &self.timestamp_us, None)?;
+        write_column::<Int64Type>(&mut group, &self.capture_time_ns, None)?;
+        write_column::<Int32Type>(&mut group, &self.sequence_id, None)?;
+        write_column::<Int64Type>(&mut group, &self.packet_index, None)?;
+        write_column::<Int32Type>(&mut group, &self.point_index, None)?;
+        write_column::<Int64Type>(&mut group, &self.frame, None)?;
+        if polar {
+            write_column::<FloatType>(&mut group, &self.range, None)?;
+            write_column::<FloatType>(&mut group, &self.azimuth, None)?;
+            write_column::<FloatType>(&mut group, &self.elevation, None)?;
+        }
+        write_column::<Int32Type>(&mut group, &self.distance, Some(&self.debug))?;
+        write_column::<Int32Type>(&mut group, &self.intensity, Some(&self.debug))?;
+        write_column::<Int32Type>(&mut group, &self.power_level, Some(&self.debug))?;
+        group.close()?;
+
+        *self = Rows::default();
+        Ok(())
+    }
+}
+
+/// Write the next column of a row group
+fn write_column<T: DataType>(
+    group: &mut SerializedRowGroupWriter<File>,
+    values: &[T::T],
+    def_levels: Option<&[i16]>,
+) -> Result<()> {
+    let mut column = group.next_column()?.context("Parquet schema has fewer columns than written")?;
+    column.typed::<T>().write_batch(values, def_levels, None)?;
+    column.close()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parquet::file::reader::{FileReader, SerializedFileReader};
+    use parquet::record::RowAccessor;
+
+    #[test]
+    fn test_parquet_export() {
+        let sensor: SocketAddr = "192.168.1.201:8808".parse().unwrap();
+        let normal = Point {
+            x: 1.5,
+            y: -2.0,
+            z: 0.25,
+            reflectivity: 200,
+            channel: 3,
+            capture_time_ns: 1_700_000_000_250_000_000,
+            packet_index: 12,
+            frame: 4,
+            ..Default::default()
+        };
+        let debug = Point { distance: Some(3_000_000_000), intensity: Some(55), power_level: Some(3), ..normal.clone() };
+
+        let options = ParquetOptions { layout: Layout::Merged, ..Default::default() };
+        let source = SourceInfo {
+            file: "capture.pcap".to_string(),
+            mode: "Debug (forced)".to_string(),
+            window: Some("sensor time 1.000 s to end".to_string()),
+            ..Default::default()
+        };
+        let mut writer = Box::new(ParquetWriter::new("test_merged.parquet", &options, &source, [sensor].into_iter()));
+        writer.write_point(ChannelKey { sensor, channel: 3 }, &normal).unwrap();
+        writer.write_point(ChannelKey { sensor, channel: 3 }, &debug).unwrap();
+        let summary = writer.finish().unwrap();
+        assert_eq!(summary.files, vec!["test_merged.parquet"]);
+
+        let reader = SerializedFileReader::new(File::open("test_merged.parquet").unwrap()).unwrap();
+        let metadata = reader.metadata().file_metadata().key_value_metadata().unwrap().clone();
+        let rows: Vec<_> = reader.get_row_iter(None).unwrap().map(|row| row.unwrap()).collect();
+        let _ = std::fs::remove_file("test_merged.parquet");
+
+        assert_eq!(metadata[0].value.as_deref(), Some("capture.pcap"));
+        assert_eq!(metadata[1].value.as_deref(), Some("Debug (forced)"));
+        assert_eq!(metadata[2].key, "time_window");
+
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].get_string(0).unwrap(), "192.168.1.201:8808");
+        assert_eq!(rows[0].get_ubyte(1).unwrap(), 3);
+        assert_eq!(rows[0].get_float(2).unwrap(), 1.5);
+        assert_eq!(rows[0].get_ubyte(5).unwrap(), 200);
+        assert_eq!(rows[0].get_ulong(8).unwrap(), 1_700_000_000_250_000_000);
+        assert_eq!(rows[0].get_ulong(10).unwrap(), 12);
+        assert_eq!(rows[0].get_ulong(12).unwrap(), 4);
+        assert!(rows[0].get_uint(13).is_err()); // null
+        assert_eq!(rows[1].get_uint(13).unwrap(), 3_000_000_000);
+        assert_eq!(rows[1].get_ushort(14).unwrap(), 55);
+        assert_eq!(rows[1].get_ubyte(15).unwrap(), 3);
+    }
+
+    #[test]
+    fn test_parquet_polar() {
+        let sensor: SocketAddr = "192.168.1.201:8808".parse().unwrap();
+        let point = Point { x: 3.0, y: 4.0, distance: Some(5000), intensity: Some(1), power_level: Some(2), ..Default::default() };
+
+        let options = ParquetOptions { layout: Layout::Merged, polar: true, ..Default::default() };
+        let mut writer =
+            Box::new(ParquetWriter::new("test_polar.parquet", &options, &SourceInfo::default(), [sensor].into_iter()));
+        writer.write_point(ChannelKey { sensor, channel: 1 }, &point).unwrap();
+        writer.finish().unwrap();
+
+        let reader = SerializedFileReader::new(File::open("test_polar.parquet").unwrap()).unwrap();
+        let rows: Vec<_> = reader.get_row_iter(None).unwrap().map(|row| row.unwrap()).collect();
+        let _ = std::fs::remove_file("test_polar.parquet");
+
+        assert_eq!(rows[0].get_float(13).unwrap(), 5.0);
+        assert!((rows[0].get_float(14).unwrap() - 36.869_9).abs() < 1e-3);
+        assert_eq!(rows[0].get_float(15).unwrap(), 0.0);
+        assert_eq!(rows[0].get_uint(16).unwrap(), 5000);
+    }
+}
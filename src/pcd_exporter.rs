@@ -0,0 +1,404 @@
+//! PCD (Point Cloud Library) export - ascii, binary and binary_compressed clouds
+//! with x, y, z, intensity, channel and timestamp fields, optionally range, azimuth and elevation.
+//! ascii / binary are streamed; the point counts in the header are patched in place
+//! once the file is complete. binary_compressed compresses each cloud as one LZF block,
+//! so the points of a file are kept in memory until it is written (25 bytes per point,
+//! 37 with the polar fields), and a file holds at most `u32::MAX` bytes of point data.
+
+use crate::cepton::{ChannelKey, Point};
+use crate::columns::Column;
+use crate::export::{ExportSummary, Layout, OpenFiles, OutputFiles, PointWriter};
+use anyhow::{Context, Result};
+use std::fs::File;
+use std::io::{BufWriter, Seek, SeekFrom, Write};
+use std::net::SocketAddr;
+
+/// DATA section encoding of a PCD file
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum PcdEncoding {
+    Ascii,
+    #[default]
+    Binary,
+    BinaryCompressed,
+}
+
+impl PcdEncoding {
+    fn name(self) -> &'static str {
+        match self {
+            PcdEncoding::Ascii => "ascii",
+            PcdEncoding::Binary => "binary",
+            PcdEncoding::BinaryCompressed => "binary_compressed",
+        }
+    }
+}
+
+/// PCD writer options
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PcdOptions {
+    pub encoding: PcdEncoding,
+    pub layout: Layout,
+    pub per_sensor_files: bool,
+    pub polar: bool, // add the range, azimuth and elevation fields
+}
+
+/// Fields of every point: (name, size, type)
+const FIELDS: [(&str, usize, char); 6] = [
+    ("x", 4, 'F'),
+    ("y", 4, 'F'),
+    ("z", 4, 'F'),
+    ("intensity", 4, 'F'), // reflectivity
+    ("channel", 1, 'U'),
+    ("timestamp", 8, 'F'), // capture time, Unix seconds
+];
+
+/// Fields added by `PcdOptions::polar`: meters, degrees, degrees
+const POLAR_FIELDS: [(&str, usize, char); 3] = [("range", 4, 'F'), ("azimuth", 4, 'F'), ("elevation", 4, 'F')];
+
+/// Fields of a file, in order
+fn fields(polar: bool) -> impl Iterator<Item = &'static (&'static str, usize, char)> {
+    let extra: &[_] = if polar { &POLAR_FIELDS } else { &[] };
+    FIELDS.iter().chain(extra)
+}
+
+/// Bytes of one point
+fn point_size(polar: bool) -> usize {
+    fields(polar).map(|(_, size, _)| size).sum()
+}
+
+/// Most points a binary_compressed file can hold: its block sizes are stored as u32
+fn max_compressed_points(polar: bool) -> u64 {
+    u32::MAX as u64 / point_size(polar) as u64
+}
+
+/// Digits reserved for WIDTH / POINTS so the header can be rewritten in place
+const COUNT_DIGITS: usize = 10;
+
+/// Streams points into one PCD file per channel, or into one merged cloud
+pub struct PcdWriter {
+    options: PcdOptions,
+    outputs: OpenFiles<PcdFile>,
+}
+
+/// One open output file
+struct PcdFile {
+    writer: BufWriter<File>,
+    points: u64,
+    columns: Vec<Vec<u8>>, // per-field data for binary_compressed
+}
+
+impl PcdWriter {
+    /// Start an export to `output_path`; `sensors` are the sensors whose points will be written
+    pub fn new(output_path: &str, options: &PcdOptions, sensors: impl Iterator<Item = SocketAddr>) -> Self {
+        let files = OutputFiles::new(output_path, options.layout, options.per_sensor_files, sensors);
+        PcdWriter {
+            options: *options,
+            outputs: OpenFiles::new(files),
+        }
+    }
+}
+
+/// Create a file with a placeholder header
+fn open(path: &str, options: &PcdOptions) -> Result<PcdFile> {
+    let file = File::create(path).with_context(|| format!("Failed to create file: {}", path))?;
+    let mut writer = BufWriter::new(file);
+    writer.write_all(header(0, options).as_bytes())?;
+
+    Ok(PcdFile {
+        writer,
+        points: 0,
+        columns: vec![Vec::new(); fields(options.polar).count()],
+    })
+}
+
+impl PointWriter for PcdWriter {
+    fn write_point(&mut self, key: ChannelKey, point: &Point) -> Result<()> {
+        let options = &self.options;
+        let encoding = options.encoding;
+        let file = self.outputs.get_or_open(key, |path| open(path, options))?;
+        let output = &mut file.output;
+        output.points += 1;
+        if encoding == PcdEncoding::BinaryCompressed && output.points > max_compressed_points(options.polar) {
+            anyhow::bail!(
+                "{}: more than {} points, too many for one binary_compressed PCD file; \
+                 use --pcd-encoding binary or split the output with --layout per-channel or --frame-files",
+                file.path,
+                max_compressed_points(options.polar)
+            );
+        }
+
+        let timestamp = Column::CaptureTime.value(point).unwrap_or_default();
+        let polar = [point.range(), point.azimuth(), point.elevation()].map(|value| value as f32);
+        let polar_bytes = polar.map(f32::to_le_bytes);
+        let fields: [&[u8]; 9] = [
+            &(point.x as f32).to_le_bytes(),
+            &(point.y as f32).to_le_bytes(),
+            &(point.z as f32).to_le_bytes(),
+            &(point.reflectivity as f32).to_le_bytes(),
+            &[point.channel],
+            &timestamp.to_le_bytes(),
+            &polar_bytes[0],
+            &polar_bytes[1],
+            &polar_bytes[2],
+        ];
+        let fields = &fields[..output.columns.len()];
+
+        match encoding {
+            PcdEncoding::Ascii => write!(
+                output.writer,
+                "{} {} {} {} {} {:.6}",
+                point.x as f32, point.y as f32, point.z as f32, point.reflectivity, point.channel, timestamp
+            )
+            .and_then(|_| match options.polar {
+                true => writeln!(output.writer, " {} {} {}", polar[0], polar[1], polar[2]),
+                false => writeln!(output.writer),
+            }),
+            PcdEncoding::Binary => fields.iter().try_for_each(|field| output.writer.write_all(field)),
+            PcdEncoding::BinaryCompressed => {
+                for (column, field) in output.columns.iter_mut().zip(fields) {
+                    column.extend_from_slice(field);
+                }
+                Ok(())
+            }
+        }
+        .with_context(|| format!("Failed to write file: {}", file.path))
+    }
+
+    fn finish(self: Box<Self>) -> Result<ExportSummary> {
+        let options = self.options;
+        let encoding = options.encoding;
+
+        let mut summary = ExportSummary::default();
+        for file in self.outputs.into_sorted() {
+            let context = || format!("Failed to write file: {}", file.path);
+            let mut output = file.output;
+
+            if encoding == PcdEncoding::BinaryCompressed {
+                // Fields are stored one after another, then compressed as a whole
+                let data = output.columns.concat();
+                let compressed = lzf_compress(&data);
+                let Ok(compressed_len) = u32::try_from(compressed.len()) else {
+                    anyhow::bail!("{}: compressed point data exceeds 4 GiB, too large for a PCD file", file.path);
+                };
+                output.writer.write_all(&compressed_len.to_le_bytes()).with_context(context)?;
+                output.writer.write_all(&(data.len() as u32).to_le_bytes()).with_context(context)?;
+                output.writer.write_all(&compressed).with_context(context)?;
+            }
+
+            // Now that the number of points is known, fill it into the header
+            let mut handle = output.writer.into_inner().map_err(|e| e.into_error()).with_context(context)?;
+            handle.seek(SeekFrom::Start(0)).with_context(context)?;
+            handle.write_all(header(output.points, &options).as_bytes()).with_context(context)?;
+
+            summary.files.push(file.path);
+        }
+
+        Ok(summary)
+    }
+}
+
+/// PCD v0.7 header; counts are zero padded so every header has the same length
+fn header(points: u64, options: &PcdOptions) -> String {
+    let names: Vec<_> = fields(options.polar).map(|(name, _, _)| name.to_string()).collect();
+    let sizes: Vec<_> = fields(options.polar).map(|(_, size, _)| size.to_string()).collect();
+    let types: Vec<_> = fields(options.polar).map(|(_, _, kind)| kind.to_string()).collect();
+
+    format!(
+        "# .PCD v0.7 - Point Cloud Data file format\n\
+         VERSION 0.7\n\
+         FIELDS {}\n\
+         SIZE {}\n\
+         TYPE {}\n\
+         COUNT {}\n\
+         WIDTH {:0width$}\n\
+         HEIGHT 1\n\
+         VIEWPOINT 0 0 0 1 0 0 0\n\
+         POINTS {:0width$}\n\
+         DATA {}\n",
+        names.join(" "),
+        sizes.join(" "),
+        types.join(" "),
+        vec!["1"; names.len()].join(" "),
+        points,
+        points,
+        options.encoding.name(),
+        width = COUNT_DIGITS,
+    )
+}
+
+/// LZF compression (liblzf format, as PCL expects for binary_compressed)
+fn lzf_compress(input: &[u8]) -> Vec<u8> {
+    const HASH_BITS: u32 = 14;
+    const MAX_OFFSET: usize = 1 << 13;
+    const MAX_MATCH: usize = (1 << 8) + (1 << 3); // 264 bytes
+
+    let mut out = Vec::with_capacity(input.len() + input.len() / 32 + 1);
+    let mut table = vec![0usize; 1 << HASH_BITS]; // last position + 1 of each hash, 0 = none
+    let mut literal_start = 0;
+    let mut i = 0;
+
+    while i + 2 < input.len() {
+        let key = u32::from_le_bytes([input[i], input[i + 1], input[i + 2], 0]);
+        let hash = (key.wrapping_mul(2_654_435_761) >> (32 - HASH_BITS)) as usize;
+        let candidate = table[hash];
+        table[hash] = i + 1;
+
+        if candidate > 0 {
+            let start = candidate - 1;
+            let offset = i - start - 1;
+            if offset < MAX_OFFSET && input[start..start + 3] == input[i..i + 3] {
+                let max_len = (input.len() - i).min(MAX_MATCH);
+                let mut len = 3;
+                while len < max_len && input[start + len] == input[i + len] {
+                    len += 1;
+                }
+
+                push_literals(&mut out, &input[literal_start..i]);
+
+                // Back reference: 3 bit length (7 = extended) and 13 bit offset
+                let encoded_len = len - 2;
+                if encoded_len < 7 {
+                    out.push(((encoded_len << 5) | (offset >> 8)) as u8);
+                } else {
+                    out.push(((7 << 5) | (offset >> 8)) as u8);
+                    out.push((encoded_len - 7) as u8);
+                }
+                out.push((offset & 0xff) as u8);
+
+                i += len;
+                literal_start = i;
+                continue;
+            }
+        }
+
+        i += 1;
+    }
+
+    push_literals(&mut out, &input[literal_start..]);
+    out
+}
+
+/// Literal runs of up to 32 bytes, each prefixed with its length - 1
+fn push_literals(out: &mut Vec<u8>, mut literals: &[u8]) {
+    while !literals.is_empty() {
+        let len = literals.len().min(32);
+        out.push((len - 1) as u8);
+        out.extend_from_slice(&literals[..len]);
+        literals = &literals[len..];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lzf_decompress(input: &[u8]) -> Vec<u8> {
+        let mut out = Vec::new();
+        let mut i = 0;
+        while i < input.len() {
+            let ctrl = input[i] as usize;
+            i += 1;
+            if ctrl < 32 {
+                out.extend_from_slice(&input[i..i + ctrl + 1]);
+                i += ctrl + 1;
+            } else {
+                let mut len = ctrl >> 5;
+                if len == 7 {
+                    len += input[i] as usize;
+                    i += 1;
+                }
+                let start = out.len() - ((ctrl & 0x1f) << 8) - input[i] as usize - 1;
+                i += 1;
+                for k in 0..len + 2 {
+                    out.push(out[start + k]);
+                }
+            }
+        }
+        out
+    }
+
+    #[test]
+    fn test_lzf_roundtrip() {
+        let mut data: Vec<u8> = (0..5000u32).flat_map(|v| (v % 97).to_le_bytes()).collect();
+        data.extend(vec![7u8; 1000]);
+        data.extend(b"ab");
+
+        let compressed = lzf_compress(&data);
+        assert!(compressed.len() < data.len() / 2);
+        assert_eq!(lzf_decompress(&compressed), data);
+
+        assert_eq!(lzf_decompress(&lzf_compress(b"xy")), b"xy");
+        assert!(lzf_compress(b"").is_empty());
+    }
+
+    #[test]
+    fn test_pcd_export() {
+        let point_size = point_size(false);
+        assert_eq!(point_size, 25);
+        assert_eq!(max_compressed_points(false), 171_798_691);
+        assert_eq!(max_compressed_points(true), 116_080_197);
+        let sensor: SocketAddr = "192.168.1.201:8808".parse().unwrap();
+        let point = Point {
+            x: 1.5,
+            y: -2.0,
+            z: 0.25,
+            reflectivity: 40,
+            channel: 3,
+            capture_time_ns: 1_700_000_000_250_000_000,
+            ..Default::default()
+        };
+
+        for encoding in [PcdEncoding::Ascii, PcdEncoding::Binary, PcdEncoding::BinaryCompressed] {
+            let options = PcdOptions { encoding, layout: Layout::Merged, ..Default::default() };
+            let path = format!("test_{}.pcd", encoding.name());
+            let mut writer = Box::new(PcdWriter::new(&path, &options, [sensor].into_iter()));
+            for channel in [3, 4] {
+                writer.write_point(ChannelKey { sensor, channel }, &point).unwrap();
+            }
+            let summary = writer.finish().unwrap();
+            assert_eq!(summary.files, vec![path.clone()]);
+
+            let bytes = std::fs::read(&path).unwrap();
+            let _ = std::fs::remove_file(&path);
+
+            let header_len = header(2, &options).len();
+            let text = String::from_utf8_lossy(&bytes[..header_len]);
+            assert!(text.contains("WIDTH 0000000002\n"));
+            assert!(text.contains("POINTS 0000000002\n"));
+            assert!(text.ends_with(&format!("DATA {}\n", encoding.name())));
+
+            let data = &bytes[header_len..];
+            match encoding {
+                PcdEncoding::Ascii => {
+                    let lines = String::from_utf8_lossy(data);
+                    assert_eq!(lines.lines().next(), Some("1.5 -2 0.25 40 3 1700000000.250000"));
+                }
+                PcdEncoding::Binary => {
+                    assert_eq!(data.len(), 2 * point_size);
+                    assert_eq!(&data[0..4], &1.5f32.to_le_bytes());
+                    assert_eq!(data[16], 3);
+                }
+                PcdEncoding::BinaryCompressed => {
+                    let compressed = u32::from_le_bytes(data[0..4].try_into().unwrap()) as usize;
+                    let size = u32::from_le_bytes(data[4..8].try_into().unwrap()) as usize;
+                    let fields = lzf_decompress(&data[8..8 + compressed]);
+                    assert_eq!(size, 2 * point_size);
+                    assert_eq!(fields.len(), size);
+                    // x of both points, then y of both points, ...
+                    assert_eq!(&fields[4..8], &1.5f32.to_le_bytes());
+                    assert_eq!(&fields[8..12], &(-2.0f32).to_le_bytes());
+                }
+            }
+        }
+
+        // Polar fields follow the timestamp
+        let options = PcdOptions { encoding: PcdEncoding::Ascii, layout: Layout::Merged, polar: true, ..Default::default() };
+        let mut writer = Box::new(PcdWriter::new("test_polar.pcd", &options, [sensor].into_iter()));
+        writer.write_point(ChannelKey { sensor, channel: 3 }, &Point { y: 2.0, ..point }).unwrap();
+        writer.finish().unwrap();
+        let text = std::fs::read_to_string("test_polar.pcd").unwrap();
+        let _ = std::fs::remove_file("test_polar.pcd");
+        assert!(text.contains("FIELDS x y z intensity channel timestamp range azimuth elevation\n"));
+        assert!(text.contains("COUNT 1 1 1 1 1 1 1 1 1\n"));
+        assert!(text.ends_with("\n1.5 2 0.25 40 3 1700000000.250000 2.5124688 36.869896 5.710593\n"));
+    }
+}
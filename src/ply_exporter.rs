@@ -0,0 +1,242 @@
+//! PLY export - ascii and binary little-endian vertex clouds for MeshLab, Blender, ...
+//! Points are streamed; the vertex count in the header is patched in place once the
+//! file is complete.
+
+use crate::cepton::{ChannelKey, Point};
+use crate::export::{ExportSummary, Layout, OpenFiles, OutputFiles, PointWriter};
+use crate::pcap_reader::SensorStats;
+use anyhow::{Context, Result};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufWriter, Seek, SeekFrom, Write};
+use std::net::SocketAddr;
+
+/// Encoding of the vertex data
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum PlyEncoding {
+    Ascii,
+    #[default]
+    BinaryLittleEndian,
+}
+
+impl PlyEncoding {
+    fn name(self) -> &'static str {
+        match self {
+            PlyEncoding::Ascii => "ascii",
+            PlyEncoding::BinaryLittleEndian => "binary_little_endian",
+        }
+    }
+}
+
+/// PLY writer options
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PlyOptions {
+    pub encoding: PlyEncoding,
+    pub layout: Layout,
+    pub per_sensor_files: bool,
+    pub polar: bool, // add the range, azimuth and elevation properties
+}
+
+/// Vertex properties of every point: (type, name)
+const PROPERTIES: [(&str, &str); 6] = [
+    ("float", "x"),
+    ("float", "y"),
+    ("float", "z"),
+    ("uchar", "reflectivity"),
+    ("uchar", "flags"),
+    ("uchar", "channel"),
+];
+
+/// Vertex properties added by `PlyOptions::polar`: meters, degrees, degrees
+const POLAR_PROPERTIES: [(&str, &str); 3] = [("float", "range"), ("float", "azimuth"), ("float", "elevation")];
+
+/// Extra vertex properties of Debug mode points
+const DEBUG_PROPERTIES: [(&str, &str); 4] = [
+    ("uchar", "has_debug"), // 0 for points read without the Debug fields
+    ("uint", "distance"),
+    ("ushort", "intensity"),
+    ("uchar", "power_level"),
+];
+
+/// Digits reserved for the vertex count so the header can be rewritten in place
+const COUNT_DIGITS: usize = 10;
+
+/// Streams points into one PLY file per channel, or into one merged cloud
+pub struct PlyWriter {
+    options: PlyOptions,
+    outputs: OpenFiles<PlyFile>,
+}
+
+/// One open output file
+struct PlyFile {
+    writer: BufWriter<File>,
+    vertices: u64,
+    debug: bool, // has the Debug mode properties
+}
+
+impl PlyWriter {
+    /// Start an export to `output_path`; `sensors` are the sensors whose points will be written
+    pub fn new(output_path: &str, options: &PlyOptions, sensors: &BTreeMap<SocketAddr, SensorStats>) -> Self {
+        let files = OutputFiles::for_sensors(output_path, options.layout, options.per_sensor_files, sensors);
+        PlyWriter {
+            options: *options,
+            outputs: OpenFiles::new(files),
+        }
+    }
+}
+
+/// Create a file with a placeholder header; `debug` adds the Debug mode properties
+fn open(path: &str, options: &PlyOptions, debug: bool) -> Result<PlyFile> {
+    let file = File::create(path).with_context(|| format!("Failed to create file: {}", path))?;
+    let mut writer = BufWriter::new(file);
+    writer.write_all(header(0, options, debug).as_bytes())?;
+
+    Ok(PlyFile { writer, vertices: 0, debug })
+}
+
+impl PointWriter for PlyWriter {
+    fn write_point(&mut self, key: ChannelKey, point: &Point) -> Result<()> {
+        let options = &self.options;
+        let debug = self.outputs.files().has_debug(key);
+        let file = self.outputs.get_or_open(key, |path| open(path, options, debug))?;
+        let output = &mut file.output;
+        output.vertices += 1;
+
+        // Points without debug data in a debug file get zeros and has_debug 0
+        let has_debug = point.distance.is_some() as u8;
+        let distance = point.distance.unwrap_or_default();
+        let intensity = point.intensity.unwrap_or_default();
+        let power_level = point.power_level.unwrap_or_default();
+        let (x, y, z) = (point.x as f32, point.y as f32, point.z as f32);
+        let polar = [point.range(), point.azimuth(), point.elevation()].map(|value| value as f32);
+
+        let result = match options.encoding {
+            PlyEncoding::Ascii => {
+                write!(output.writer, "{} {} {} {} {} {}", x, y, z, point.reflectivity, point.flags, point.channel)
+                    .and_then(|_| match options.polar {
+                        true => write!(output.writer, " {} {} {}", polar[0], polar[1], polar[2]),
+                        false => Ok(()),
+                    })
+                    .and_then(|_| match output.debug {
+                        true => writeln!(output.writer, " {} {} {} {}", has_debug, distance, intensity, power_level),
+                        false => writeln!(output.writer),
+                    })
+            }
+            PlyEncoding::BinaryLittleEndian => {
+                let mut vertex = Vec::with_capacity(35);
+                vertex.extend_from_slice(&x.to_le_bytes());
+                vertex.extend_from_slice(&y.to_le_bytes());
+                vertex.extend_from_slice(&z.to_le_bytes());
+                vertex.extend_from_slice(&[point.reflectivity, point.flags, point.channel]);
+                if options.polar {
+                    polar.iter().for_each(|value| vertex.extend_from_slice(&value.to_le_bytes()));
+                }
+                if output.debug {
+                    vertex.push(has_debug);
+                    vertex.extend_from_slice(&distance.to_le_bytes());
+                    vertex.extend_from_slice(&intensity.to_le_bytes());
+                    vertex.push(power_level);
+                }
+                output.writer.write_all(&vertex)
+            }
+        };
+
+        result.with_context(|| format!("Failed to write file: {}", file.path))
+    }
+
+    fn finish(self: Box<Self>) -> Result<ExportSummary> {
+        let options = self.options;
+
+        let mut summary = ExportSummary::default();
+        for file in self.outputs.into_sorted() {
+            let context = || format!("Failed to write file: {}", file.path);
+            let output = file.output;
+
+            // Now that the number of vertices is known, fill it into the header
+            let mut handle = output.writer.into_inner().map_err(|e| e.into_error()).with_context(context)?;
+            handle.seek(SeekFrom::Start(0)).with_context(context)?;
+            handle
+                .write_all(header(output.vertices, &options, output.debug).as_bytes())
+                .with_context(context)?;
+
+            summary.files.push(file.path);
+        }
+
+        Ok(summary)
+    }
+}
+
+/// PLY header; the vertex count is zero padded so every header has the same length
+fn header(vertices: u64, options: &PlyOptions, debug: bool) -> String {
+    let mut header = format!(
+        "ply\nformat {} 1.0\ncomment Cepton LiDAR points, coordinates in meters\nelement vertex {:0width$}\n",
+        options.encoding.name(),
+        vertices,
+        width = COUNT_DIGITS,
+    );
+
+    let polar: &[(&str, &str)] = if options.polar { &POLAR_PROPERTIES } else { &[] };
+    let extra: &[(&str, &str)] = if debug { &DEBUG_PROPERTIES } else { &[] };
+    for (kind, name) in PROPERTIES.iter().chain(polar).chain(extra) {
+        header.push_str(&format!("property {} {}\n", kind, name));
+    }
+
+    header.push_str("end_header\n");
+    header
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ply_export() {
+        let sensor: SocketAddr = "192.168.1.201:8808".parse().unwrap();
+        let normal = Point { x: 1.5, y: -2.0, z: 0.25, reflectivity: 40, flags: 2, channel: 3, ..Default::default() };
+        let debug = Point { distance: Some(1234), intensity: Some(55), power_level: Some(3), ..normal.clone() };
+
+        // ascii, one file per channel
+        let options = PlyOptions { encoding: PlyEncoding::Ascii, ..Default::default() };
+        let sensors = BTreeMap::from([(sensor, SensorStats::default())]);
+        let mut writer = Box::new(PlyWriter::new("test_ascii.ply", &options, &sensors));
+        writer.write_point(ChannelKey { sensor, channel: 3 }, &normal).unwrap();
+        writer.write_point(ChannelKey { sensor, channel: 3 }, &normal).unwrap();
+        let summary = writer.finish().unwrap();
+
+        assert_eq!(summary.files, vec!["test_ascii_Channel_3.ply"]);
+        let text = std::fs::read_to_string("test_ascii_Channel_3.ply").unwrap();
+        let _ = std::fs::remove_file("test_ascii_Channel_3.ply");
+        assert!(text.starts_with("ply\nformat ascii 1.0\n"));
+        assert!(text.contains("element vertex 0000000002\n"));
+        assert!(!text.contains("distance"));
+        assert!(text.ends_with("end_header\n1.5 -2 0.25 40 2 3\n1.5 -2 0.25 40 2 3\n"));
+
+        // binary with the polar and debug properties
+        let options = PlyOptions { layout: Layout::Merged, polar: true, ..Default::default() };
+        let sensors = BTreeMap::from([(sensor, SensorStats { debug: true, ..Default::default() })]);
+        let mut writer = Box::new(PlyWriter::new("test_binary.ply", &options, &sensors));
+        writer.write_point(ChannelKey { sensor, channel: 4 }, &normal).unwrap();
+        writer.write_point(ChannelKey { sensor, channel: 3 }, &debug).unwrap();
+        let summary = writer.finish().unwrap();
+
+        assert_eq!(summary.files, vec!["test_binary.ply"]);
+        let bytes = std::fs::read("test_binary.ply").unwrap();
+        let _ = std::fs::remove_file("test_binary.ply");
+
+        let header_len = header(2, &options, true).len();
+        let text = String::from_utf8_lossy(&bytes[..header_len]);
+        assert!(text.contains("property uchar channel\nproperty float range\nproperty float azimuth\nproperty float elevation\n"));
+        assert!(text.contains("property uchar has_debug\nproperty uint distance\nproperty ushort intensity\nproperty uchar power_level\n"));
+
+        // The first point has no debug data, yet the debug properties come from the scan
+        let data = &bytes[header_len..];
+        assert_eq!(data.len(), 2 * 35);
+        assert_eq!(&data[0..4], &1.5f32.to_le_bytes());
+        assert_eq!(&data[12..15], &[40, 2, 3]);
+        assert_eq!(&data[15..19], &(normal.range() as f32).to_le_bytes());
+        assert_eq!(&data[19..23], &(normal.azimuth() as f32).to_le_bytes());
+        assert_eq!(&data[27..32], &[0, 0, 0, 0, 0]); // has_debug 0, distance 0
+        assert_eq!(data[35 + 27], 1);
+        assert_eq!(&data[35 + 28..35 + 32], &1234u32.to_le_bytes());
+    }
+}
@@ -0,0 +1,150 @@
+//! Spatial region of interest - keeps only the points inside a box, a cylinder,
+//! a range band or an azimuth / elevation sector, all in one coordinate frame
+
+use crate::cepton::{self, Point};
+use crate::transform::CoordFrame;
+use std::fmt;
+
+/// One region; bounds are inclusive, lengths in meters, angles in degrees
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Region {
+    /// Axis-aligned box
+    Box { min: [f64; 3], max: [f64; 3] },
+    /// Vertical cylinder shell around the frame's Z axis: horizontal distance and height
+    Cylinder { radius: (f64, f64), z: (f64, f64) },
+    /// Spherical shell: Euclidean distance from the frame's origin
+    Range { min: f64, max: f64 },
+    /// Angular sector; an azimuth range with min > max wraps through ±180
+    Sector { azimuth: (f64, f64), elevation: (f64, f64) },
+}
+
+impl Region {
+    /// Whether a position, meters, lies in the region
+    pub fn contains(&self, position: [f64; 3]) -> bool {
+        let within = |value: f64, (min, max): (f64, f64)| min <= value && value <= max;
+        let [x, y, z] = position;
+
+        match *self {
+            Region::Box { min, max } => position.iter().enumerate().all(|(i, &v)| within(v, (min[i], max[i]))),
+            Region::Cylinder { radius, z: height } => within(x.hypot(y), radius) && within(z, height),
+            Region::Range { min, max } => within(cepton::range(position), (min, max)),
+            Region::Sector { azimuth: (from, to), elevation } => {
+                let azimuth = cepton::azimuth(position);
+                let in_azimuth = if from <= to {
+                    within(azimuth, (from, to))
+                } else {
+                    azimuth >= from || azimuth <= to
+                };
+                in_azimuth && within(cepton::elevation(position), elevation)
+            }
+        }
+    }
+}
+
+impl fmt::Display for Region {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Region::Box { min, max } => write!(
+                f,
+                "box x {}..{} m, y {}..{} m, z {}..{} m",
+                min[0], max[0], min[1], max[1], min[2], max[2]
+            ),
+            Region::Cylinder { radius, z } => {
+                write!(f, "cylinder radius {}..{} m", radius.0, radius.1)?;
+                if z.0.is_finite() || z.1.is_finite() {
+                    write!(f, ", z {}..{} m", z.0, z.1)?;
+                }
+                Ok(())
+            }
+            Region::Range { min, max } => write!(f, "range {}..{} m", min, max),
+            Region::Sector { azimuth, elevation } => {
+                write!(f, "azimuth {}..{}°", azimuth.0, azimuth.1)?;
+                if elevation.0 > -90.0 || elevation.1 < 90.0 {
+                    write!(f, ", elevation {}..{}°", elevation.0, elevation.1)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Regions a point must lie in, all of them; empty keeps every point
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RegionFilter {
+    pub regions: Vec<Region>,
+    pub frame: CoordFrame, // frame every region is tested in
+}
+
+impl RegionFilter {
+    pub fn contains(&self, point: &Point) -> bool {
+        let position = point.position(self.frame);
+        self.regions.iter().all(|region| region.contains(position))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.regions.is_empty()
+    }
+}
+
+impl fmt::Display for RegionFilter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.regions.is_empty() {
+            return write!(f, "none");
+        }
+        let regions: Vec<String> = self.regions.iter().map(Region::to_string).collect();
+        write!(f, "{}", regions.join(" and "))?;
+        if self.frame == CoordFrame::Sensor {
+            write!(f, ", in the sensor frame")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn point(x: f64, y: f64, z: f64) -> Point {
+        Point { x, y, z, ..Default::default() }
+    }
+
+    #[test]
+    fn test_regions() {
+        let target = Region::Box { min: [-0.5, 4.0, -1.0], max: [0.5, 6.0, 1.0] };
+        assert!(target.contains([0.0, 5.0, 0.0]));
+        assert!(target.contains([0.5, 4.0, 1.0])); // bounds are inclusive
+        assert!(!target.contains([0.0, 6.5, 0.0]));
+
+        let cylinder = Region::Cylinder { radius: (1.0, 2.0), z: (f64::NEG_INFINITY, f64::INFINITY) };
+        assert!(cylinder.contains([1.5, 0.0, 100.0]));
+        assert!(!cylinder.contains([0.5, 0.5, 0.0]));
+
+        let range = Region::Range { min: 3.0, max: 5.0 };
+        assert!(range.contains([0.0, 3.0, 4.0]));
+        assert!(!range.contains([0.0, 2.0, 0.0]));
+
+        // Sector behind the sensor, through ±180°
+        let behind = Region::Sector { azimuth: (170.0, -170.0), elevation: (-90.0, 90.0) };
+        assert!(behind.contains([0.0, -5.0, 0.0]));
+        assert!(behind.contains([-0.1, -5.0, 0.0]));
+        assert!(!behind.contains([5.0, 0.0, 0.0]));
+
+        let ahead = Region::Sector { azimuth: (-30.0, 30.0), elevation: (0.0, 10.0) };
+        assert!(ahead.contains([1.0, 10.0, 0.5]));
+        assert!(!ahead.contains([1.0, 10.0, -0.5]));
+
+        let mut filter = RegionFilter { regions: vec![ahead, range], frame: CoordFrame::Vehicle };
+        assert!(!filter.contains(&point(1.0, 10.0, 0.5)));
+        assert!(filter.contains(&point(0.0, 4.0, 0.1)));
+        assert!(RegionFilter::default().contains(&point(0.0, 0.0, 0.0)));
+        assert_eq!(filter.to_string(), "azimuth -30..30°, elevation 0..10° and range 3..5 m");
+
+        // A transformed point is tested in one frame, whichever region it is
+        let mut moved = point(0.0, 4.0, 0.1);
+        moved.sensor_xyz = Some([0.0, 20.0, 0.1]);
+        assert!(filter.contains(&moved));
+        filter.frame = CoordFrame::Sensor;
+        assert!(!filter.contains(&moved));
+        assert!(filter.to_string().ends_with(", in the sensor frame"));
+    }
+}
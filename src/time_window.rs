@@ -0,0 +1,274 @@
+//! Time-window selection - which packets lie between a start and an end time,
+//! on the capture clock, the sensor clock or relative to the first packet
+
+use std::fmt;
+
+const SECOND_NS: u64 = 1_000_000_000;
+const DAY_NS: u64 = 86_400 * SECOND_NS;
+
+/// Clock the window bounds are read from
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum TimeBase {
+    Capture, // pcap capture time (Unix time)
+    Sensor,  // STDV header timestamp (time since sensor power-up)
+    #[default]
+    Offset,  // capture time since the first packet
+}
+
+impl fmt::Display for TimeBase {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TimeBase::Capture => write!(f, "capture time"),
+            TimeBase::Sensor => write!(f, "sensor time"),
+            TimeBase::Offset => write!(f, "time after first packet"),
+        }
+    }
+}
+
+/// One end of a time window
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TimeBound {
+    Nanos(u64),     // nanoseconds on the window's clock; Unix time for capture time
+    TimeOfDay(u64), // nanoseconds after UTC midnight, on the day of the first packet (capture time only)
+}
+
+/// Packets to extract; a missing bound leaves that side open
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct TimeWindow {
+    pub base: TimeBase,
+    pub start: Option<TimeBound>,
+    pub end: Option<TimeBound>,
+}
+
+impl TimeWindow {
+    /// True when the window lets every packet through
+    pub fn is_empty(&self) -> bool {
+        self.start.is_none() && self.end.is_none()
+    }
+
+    /// Inclusive (start, end) in nanoseconds on the window's clock,
+    /// with times of day placed relative to the first packet
+    fn resolve(&self, first_capture_ns: u64) -> (u64, u64) {
+        let start = self.start.map_or(0, |bound| time_of_day_near(bound, first_capture_ns));
+        let mut end = self.end.map_or(u64::MAX, |bound| time_of_day_near(bound, first_capture_ns));
+
+        // "23:59:50" to "00:00:10" crosses midnight
+        if let Some(TimeBound::TimeOfDay(_)) = self.end
+            && end < start
+        {
+            end += DAY_NS;
+        }
+
+        (start, end)
+    }
+}
+
+/// A time of day becomes the instant within 12 hours of `first_capture_ns`
+fn time_of_day_near(bound: TimeBound, first_capture_ns: u64) -> u64 {
+    match bound {
+        TimeBound::Nanos(nanos) => nanos,
+        TimeBound::TimeOfDay(nanos) => {
+            let at = first_capture_ns - first_capture_ns % DAY_NS + nanos;
+            if at + DAY_NS / 2 < first_capture_ns {
+                at + DAY_NS
+            } else if at >= first_capture_ns + DAY_NS / 2 {
+                at.saturating_sub(DAY_NS)
+            } else {
+                at
+            }
+        }
+    }
+}
+
+impl fmt::Display for TimeWindow {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let bound = |bound: Option<TimeBound>, open: &str| match (bound, self.base) {
+            (None, _) => open.to_string(),
+            (Some(TimeBound::TimeOfDay(nanos)), _) => format!("{} UTC", &utc_datetime(nanos)[11..]),
+            (Some(TimeBound::Nanos(nanos)), TimeBase::Capture) => format!("{} UTC", utc_datetime(nanos)),
+            (Some(TimeBound::Nanos(nanos)), _) => format!("{:.3} s", nanos as f64 / 1e9),
+        };
+        write!(f, "{} {} to {}", self.base, bound(self.start, "start"), bound(self.end, "end"))
+    }
+}
+
+/// Decides packet by packet whether it lies in the window. Offsets and times of day
+/// are taken relative to the first packet passed in, so the scan and the extraction
+/// must pass the same packets.
+#[derive(Debug)]
+pub struct WindowClock {
+    window: TimeWindow,
+    first: Option<(u64, (u64, u64))>, // capture time of the first packet, resolved window
+}
+
+impl WindowClock {
+    pub fn new(window: &TimeWindow) -> Self {
+        WindowClock { window: *window, first: None }
+    }
+
+    /// True when a packet captured at `capture_ns` (Unix ns) with header timestamp
+    /// `sensor_us` lies in the window
+    pub fn contains(&mut self, capture_ns: u64, sensor_us: u64) -> bool {
+        if self.window.is_empty() {
+            return true;
+        }
+
+        let window = &self.window;
+        let &mut (first, (start, end)) = self.first.get_or_insert_with(|| (capture_ns, window.resolve(capture_ns)));
+        let time = match self.window.base {
+            TimeBase::Capture => capture_ns,
+            TimeBase::Sensor => sensor_us.saturating_mul(1000),
+            TimeBase::Offset => capture_ns.saturating_sub(first),
+        };
+
+        (start..=end).contains(&time)
+    }
+}
+
+/// Parse a wall-clock time: "[YYYY-MM-DD(T| )]HH:MM:SS[.fff][Z|+HH:MM|-HH:MM]", UTC unless
+/// an offset is given. Without a date the result is a time of day.
+pub fn parse_datetime(s: &str) -> Option<TimeBound> {
+    let s = s.trim();
+    let (date, time) = match s.split_once(['T', ' ']) {
+        Some((date, time)) => (Some(date), time),
+        None => (None, s),
+    };
+
+    // UTC offset, subtracted to get UTC
+    let (time, offset_ns) = if let Some(time) = time.strip_suffix('Z') {
+        (time, 0)
+    } else if let Some(at) = time.find(['+', '-']) {
+        let (time, zone) = time.split_at(at);
+        let (hours, minutes) = zone[1..].split_once(':').unwrap_or((&zone[1..], "0"));
+        let (hours, minutes) = (hours.parse::<u64>().ok()?, minutes.parse::<u64>().ok()?);
+        if hours > 14 || minutes > 59 {
+            return None;
+        }
+        let offset = ((hours * 60 + minutes) * 60 * SECOND_NS) as i64;
+        (time, if zone.starts_with('-') { -offset } else { offset })
+    } else {
+        (time, 0)
+    };
+
+    let mut fields = time.split(':');
+    let hours: u64 = fields.next()?.parse().ok()?;
+    let minutes: u64 = fields.next()?.parse().ok()?;
+    let seconds: f64 = fields.next()?.parse().ok()?;
+    if fields.next().is_some() || hours > 23 || minutes > 59 || !(0.0..60.0).contains(&seconds) {
+        return None;
+    }
+    let local = (hours * 3600 + minutes * 60) * SECOND_NS + (seconds * 1e9).round() as u64;
+
+    match date {
+        Some(date) => {
+            let mut fields = date.split('-');
+            let year: i64 = fields.next()?.parse().ok()?;
+            let month: u32 = fields.next()?.parse().ok()?;
+            let day: u32 = fields.next()?.parse().ok()?;
+            if fields.next().is_some() || !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+                return None;
+            }
+            let days = u64::try_from(days_from_civil(year, month, day)).ok()?;
+            let utc = (days * DAY_NS + local) as i64 - offset_ns;
+            Some(TimeBound::Nanos(u64::try_from(utc).ok()?))
+        }
+        None => Some(TimeBound::TimeOfDay((local as i64 - offset_ns).rem_euclid(DAY_NS as i64) as u64)),
+    }
+}
+
+/// Days since 1970-01-01 of a proleptic Gregorian date
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month = month as i64;
+    let day_of_year = (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + day as i64 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// "YYYY-MM-DD HH:MM:SS.fff" of a Unix time in nanoseconds
+pub fn utc_datetime(unix_ns: u64) -> String {
+    let days = (unix_ns / DAY_NS) as i64 + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    let millis = unix_ns % DAY_NS / 1_000_000;
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02}.{:03}",
+        year,
+        month,
+        day,
+        millis / 3_600_000,
+        millis / 60_000 % 60,
+        millis / 1000 % 60,
+        millis % 1000
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 2024-05-01 06:32:05 UTC
+    const EVENT_NS: u64 = 1_714_545_125 * SECOND_NS;
+
+    #[test]
+    fn test_parse_datetime() {
+        assert_eq!(parse_datetime("2024-05-01T06:32:05Z"), Some(TimeBound::Nanos(EVENT_NS)));
+        assert_eq!(parse_datetime("2024-05-01 14:32:05+08:00"), Some(TimeBound::Nanos(EVENT_NS)));
+        assert_eq!(parse_datetime("2024-05-01 06:32:05.250"), Some(TimeBound::Nanos(EVENT_NS + 250_000_000)));
+        assert_eq!(
+            parse_datetime("14:32:05+08:00"),
+            Some(TimeBound::TimeOfDay((6 * 3600 + 32 * 60 + 5) * SECOND_NS))
+        );
+        assert_eq!(parse_datetime("02:00:00+08:00"), Some(TimeBound::TimeOfDay(18 * 3600 * SECOND_NS)));
+        assert_eq!(parse_datetime("24:00:00"), None);
+        assert_eq!(parse_datetime("2024-13-01 00:00:00"), None);
+        assert_eq!(parse_datetime("12.5"), None);
+
+        assert_eq!(utc_datetime(EVENT_NS + 250_000_000), "2024-05-01 06:32:05.250");
+        assert_eq!(utc_datetime(0), "1970-01-01 00:00:00.000");
+    }
+
+    #[test]
+    fn test_window_clock() {
+        // Offset: seconds 2 to 3 after the first packet
+        let window = TimeWindow {
+            base: TimeBase::Offset,
+            start: Some(TimeBound::Nanos(2 * SECOND_NS)),
+            end: Some(TimeBound::Nanos(3 * SECOND_NS)),
+        };
+        let mut clock = WindowClock::new(&window);
+        let inside: Vec<bool> = [0, 1, 2, 3, 4].iter().map(|s| clock.contains(EVENT_NS + s * SECOND_NS, 0)).collect();
+        assert_eq!(inside, [false, false, true, true, false]);
+
+        // Sensor time is read from the header timestamp
+        let window = TimeWindow { base: TimeBase::Sensor, end: Some(TimeBound::Nanos(SECOND_NS)), ..Default::default() };
+        let mut clock = WindowClock::new(&window);
+        assert!(clock.contains(EVENT_NS, 1_000_000));
+        assert!(!clock.contains(EVENT_NS, 1_000_001));
+
+        // Times of day across midnight, on the day of the first packet
+        let window = TimeWindow {
+            base: TimeBase::Capture,
+            start: parse_datetime("23:59:59"),
+            end: parse_datetime("00:00:01"),
+        };
+        let midnight = 1_714_608_000 * SECOND_NS; // 2024-05-02 00:00:00 UTC
+        let mut clock = WindowClock::new(&window);
+        assert!(!clock.contains(midnight - 10 * SECOND_NS, 0));
+        assert!(clock.contains(midnight - SECOND_NS, 0));
+        assert!(clock.contains(midnight + SECOND_NS, 0));
+        assert!(!clock.contains(midnight + 2 * SECOND_NS, 0));
+
+        assert!(WindowClock::new(&TimeWindow::default()).contains(0, 0));
+        assert_eq!(window.to_string(), "capture time 23:59:59.000 UTC to 00:00:01.000 UTC");
+    }
+}
@@ -0,0 +1,292 @@
+//! Sensor extrinsics - rigid transforms from a sensor's frame into the vehicle frame,
+//! applied to every point right after it is converted to meters
+
+use crate::cepton::Point;
+use std::fmt;
+use std::net::{IpAddr, SocketAddr};
+
+/// How far a matrix may be from a rotation and still be accepted
+const RIGID_TOLERANCE: f64 = 1e-3;
+
+/// Coordinate frame of a point position
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum CoordFrame {
+    Sensor,
+    #[default]
+    Vehicle, // after the sensor's transform; the sensor frame when it has none
+}
+
+impl fmt::Display for CoordFrame {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CoordFrame::Sensor => write!(f, "sensor"),
+            CoordFrame::Vehicle => write!(f, "vehicle"),
+        }
+    }
+}
+
+/// Rotation then translation: p' = R p + t. Angles are in degrees, right-handed,
+/// in the sensor frame (+X right, +Y forward, +Z up): roll about +Y, pitch about +X,
+/// yaw about +Z, applied as R = Rz(yaw) Rx(pitch) Ry(roll).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Transform {
+    rotation: [[f64; 3]; 3],
+    translation: [f64; 3],
+}
+
+impl Transform {
+    /// Transform from a mounting position (meters) and orientation (degrees)
+    pub fn from_pose(translation: [f64; 3], roll: f64, pitch: f64, yaw: f64) -> Self {
+        let (sr, cr) = roll.to_radians().sin_cos();
+        let (sp, cp) = pitch.to_radians().sin_cos();
+        let (sy, cy) = yaw.to_radians().sin_cos();
+
+        let rotation = [
+            [cy * cr - sy * sp * sr, -sy * cp, cy * sr + sy * sp * cr],
+            [sy * cr + cy * sp * sr, cy * cp, sy * sr - cy * sp * cr],
+            [-cp * sr, sp, cp * cr],
+        ];
+        Transform { rotation, translation }
+    }
+
+    /// Transform from a row-major 4x4 homogeneous matrix; `None` unless it is rigid
+    /// (orthonormal rotation without reflection, last row 0 0 0 1)
+    pub fn from_matrix(m: [f64; 16]) -> Option<Self> {
+        let rotation = [[m[0], m[1], m[2]], [m[4], m[5], m[6]], [m[8], m[9], m[10]]];
+        let translation = [m[3], m[7], m[11]];
+
+        let near = |value: f64, expected: f64| (value - expected).abs() <= RIGID_TOLERANCE;
+        let bottom = near(m[12], 0.0) && near(m[13], 0.0) && near(m[14], 0.0) && near(m[15], 1.0);
+        let orthonormal = (0..3).all(|i| {
+            (0..3).all(|j| {
+                let dot: f64 = (0..3).map(|k| rotation[i][k] * rotation[j][k]).sum();
+                near(dot, if i == j { 1.0 } else { 0.0 })
+            })
+        });
+        let [a, b, c] = rotation;
+        let determinant =
+            a[0] * (b[1] * c[2] - b[2] * c[1]) - a[1] * (b[0] * c[2] - b[2] * c[0]) + a[2] * (b[0] * c[1] - b[1] * c[0]);
+
+        (bottom && orthonormal && determinant > 0.0).then_some(Transform { rotation, translation })
+    }
+
+    /// Move a point into the target frame; its sensor frame position is kept for the polar values
+    pub fn apply(&self, point: &mut Point) {
+        let p = [point.x, point.y, point.z];
+        point.sensor_xyz.get_or_insert(p);
+        let [x, y, z] = [0, 1, 2].map(|i| {
+            let row = self.rotation[i];
+            row[0] * p[0] + row[1] * p[1] + row[2] * p[2] + self.translation[i]
+        });
+        (point.x, point.y, point.z) = (x, y, z);
+    }
+
+    /// (roll, pitch, yaw) in degrees; at ±90° pitch the roll is folded into the yaw
+    pub fn angles(&self) -> (f64, f64, f64) {
+        let r = &self.rotation;
+        let pitch = r[2][1].clamp(-1.0, 1.0).asin();
+        if pitch.cos() > 1e-9 {
+            let roll = (-r[2][0]).atan2(r[2][2]);
+            let yaw = (-r[0][1]).atan2(r[1][1]);
+            (roll.to_degrees(), pitch.to_degrees(), yaw.to_degrees())
+        } else {
+            (0.0, pitch.to_degrees(), r[1][0].atan2(r[0][0]).to_degrees())
+        }
+    }
+}
+
+impl fmt::Display for Transform {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // Rounded so that 90 does not show as 89.99999999999999; + 0.0 turns -0 into 0
+        let round = |value: f64| (value * 1e6).round() / 1e6 + 0.0;
+        let [x, y, z] = self.translation.map(round);
+        let (roll, pitch, yaw) = self.angles();
+        write!(
+            f,
+            "translation ({}, {}, {}) m, roll {}°, pitch {}°, yaw {}°",
+            x,
+            y,
+            z,
+            round(roll),
+            round(pitch),
+            round(yaw)
+        )
+    }
+}
+
+/// Sensors a transform is for: one address, or every port of an IP
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SensorId {
+    Ip(IpAddr),
+    Addr(SocketAddr),
+}
+
+impl fmt::Display for SensorId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SensorId::Ip(ip) => write!(f, "{}", ip),
+            SensorId::Addr(addr) => write!(f, "{}", addr),
+        }
+    }
+}
+
+/// Transform of each sensor; an address beats an IP, which beats the default
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SensorTransforms {
+    pub default: Option<Transform>, // sensors without a transform of their own
+    pub sensors: Vec<(SensorId, Transform)>,
+}
+
+impl SensorTransforms {
+    /// Set the transform of `sensor`, or the default; replaces an earlier one
+    pub fn insert(&mut self, sensor: Option<SensorId>, transform: Transform) {
+        match sensor {
+            None => self.default = Some(transform),
+            Some(sensor) => match self.sensors.iter_mut().find(|(id, _)| *id == sensor) {
+                Some(entry) => entry.1 = transform,
+                None => self.sensors.push((sensor, transform)),
+            },
+        }
+    }
+
+    /// Add the transforms of `other`, replacing ours for the same sensors
+    pub fn extend(&mut self, other: &SensorTransforms) {
+        for &(sensor, transform) in &other.sensors {
+            self.insert(Some(sensor), transform);
+        }
+        if let Some(transform) = other.default {
+            self.default = Some(transform);
+        }
+    }
+
+    /// Transform of the points of `sensor`, if any
+    pub fn get(&self, sensor: SocketAddr) -> Option<&Transform> {
+        let find = |wanted: SensorId| self.sensors.iter().find(|(id, _)| *id == wanted).map(|(_, t)| t);
+        find(SensorId::Addr(sensor))
+            .or_else(|| find(SensorId::Ip(sensor.ip())))
+            .or(self.default.as_ref())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.default.is_none() && self.sensors.is_empty()
+    }
+
+    /// "<sensor>: <transform>; ..." for each of `sensors`, for the output metadata
+    pub fn describe(&self, sensors: impl Iterator<Item = SocketAddr>) -> String {
+        let described: Vec<String> = sensors
+            .map(|sensor| match self.get(sensor) {
+                Some(transform) => format!("{}: {}", sensor, transform),
+                None => format!("{}: none", sensor),
+            })
+            .collect();
+        described.join("; ")
+    }
+}
+
+impl fmt::Display for SensorTransforms {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_empty() {
+            return write!(f, "none");
+        }
+        let mut described: Vec<String> = self.sensors.iter().map(|(id, t)| format!("{}: {}", id, t)).collect();
+        if let Some(transform) = &self.default {
+            described.push(format!("other sensors: {}", transform));
+        }
+        write!(f, "{}", described.join("; "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn point(x: f64, y: f64, z: f64) -> Point {
+        Point { x, y, z, ..Default::default() }
+    }
+
+    fn assert_near(point: &Point, expected: [f64; 3]) {
+        let actual = [point.x, point.y, point.z];
+        assert!(actual.iter().zip(expected).all(|(a, e)| (a - e).abs() < 1e-9), "{:?} != {:?}", actual, expected);
+    }
+
+    #[test]
+    fn test_pose() {
+        // Yaw 90°: forward (+Y) turns to the left (-X), then the sensor offset is added
+        let mut p = point(0.0, 10.0, 0.0);
+        Transform::from_pose([1.0, 2.0, 1.5], 0.0, 0.0, 90.0).apply(&mut p);
+        assert_near(&p, [-9.0, 2.0, 1.5]);
+        assert_eq!((p.range(), p.azimuth()), (10.0, 0.0)); // still seen from the sensor
+
+        // Pitch 10° up raises points ahead of the sensor
+        let mut p = point(0.0, 10.0, 0.0);
+        Transform::from_pose([0.0; 3], 0.0, 10.0, 0.0).apply(&mut p);
+        assert!(p.z > 1.7 && (p.y - 10.0 * 10f64.to_radians().cos()).abs() < 1e-9);
+
+        // Roll: +Z tilts towards +X
+        let mut p = point(0.0, 0.0, 1.0);
+        Transform::from_pose([0.0; 3], 90.0, 0.0, 0.0).apply(&mut p);
+        assert_near(&p, [1.0, 0.0, 0.0]);
+
+        let transform = Transform::from_pose([1.0, -0.5, 2.0], 1.5, -2.0, 30.0);
+        let (roll, pitch, yaw) = transform.angles();
+        assert!((roll - 1.5).abs() < 1e-9 && (pitch + 2.0).abs() < 1e-9 && (yaw - 30.0).abs() < 1e-9);
+        assert_eq!(transform.to_string(), "translation (1, -0.5, 2) m, roll 1.5°, pitch -2°, yaw 30°");
+    }
+
+    #[test]
+    fn test_matrix() {
+        let yaw_90 = [
+            0.0, -1.0, 0.0, 1.0,
+            1.0,  0.0, 0.0, 2.0,
+            0.0,  0.0, 1.0, 1.5,
+            0.0,  0.0, 0.0, 1.0,
+        ];
+        let transform = Transform::from_matrix(yaw_90).unwrap();
+        let mut p = point(0.0, 10.0, 0.0);
+        transform.apply(&mut p);
+        assert_near(&p, [-9.0, 2.0, 1.5]);
+        assert_eq!(transform.to_string(), "translation (1, 2, 1.5) m, roll 0°, pitch 0°, yaw 90°");
+
+        let mut scaled = yaw_90;
+        scaled[0] = 2.0;
+        assert!(Transform::from_matrix(scaled).is_none());
+        let mut mirrored = yaw_90;
+        mirrored[10] = -1.0;
+        assert!(Transform::from_matrix(mirrored).is_none());
+        let mut projective = yaw_90;
+        projective[12] = 1.0;
+        assert!(Transform::from_matrix(projective).is_none());
+    }
+
+    #[test]
+    fn test_sensor_transforms() {
+        let a: SocketAddr = "192.168.1.201:8808".parse().unwrap();
+        let b: SocketAddr = "192.168.1.202:8808".parse().unwrap();
+        let left = Transform::from_pose([-1.0, 0.0, 0.0], 0.0, 0.0, 45.0);
+        let right = Transform::from_pose([1.0, 0.0, 0.0], 0.0, 0.0, -45.0);
+
+        let mut transforms = SensorTransforms::default();
+        assert!(transforms.is_empty() && transforms.get(a).is_none());
+
+        transforms.insert(Some(SensorId::Ip(a.ip())), left);
+        transforms.insert(Some(SensorId::Addr(a)), right);
+        assert_eq!(transforms.get(a), Some(&right)); // address beats IP
+        assert_eq!(transforms.get("192.168.1.201:9000".parse().unwrap()), Some(&left));
+        assert_eq!(transforms.get(b), None);
+
+        transforms.insert(None, left);
+        assert_eq!(transforms.get(b), Some(&left));
+
+        let mut from_file = SensorTransforms::default();
+        from_file.insert(Some(SensorId::Addr(a)), left);
+        from_file.insert(Some(SensorId::Addr(b)), right);
+        from_file.extend(&transforms);
+        assert_eq!(from_file.get(a), Some(&right)); // replaced
+        assert_eq!(from_file.get(b), Some(&right)); // kept
+        assert_eq!(from_file.default, Some(left));
+        assert_eq!(
+            transforms.describe([b].into_iter()),
+            "192.168.1.202:8808: translation (-1, 0, 0) m, roll 0°, pitch 0°, yaw 45°"
+        );
+    }
+}